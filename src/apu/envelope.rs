@@ -0,0 +1,66 @@
+// volume envelope of the pulse and noise channels
+//
+// 7  bit  0
+// ---- ----
+// --LC VVVV
+//   ||+++++- volume, or period of the decay
+//   |+------- constant volume
+//   +-------- loop the decay (also halts the length counter)
+pub struct Envelope {
+    start: bool,
+    divider: u8,
+    decay: u8,
+    looping: bool,
+    constant: bool,
+    volume: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Envelope {
+            start: false,
+            divider: 0,
+            decay: 0,
+            looping: false,
+            constant: false,
+            volume: 0,
+        }
+    }
+
+    pub fn write(&mut self, data: u8) {
+        self.looping = data & 0b0010_0000 != 0;
+        self.constant = data & 0b0001_0000 != 0;
+        self.volume = data & 0b0000_1111;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    // clocked by the quarter frame
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+            return;
+        }
+        if self.divider > 0 {
+            self.divider -= 1;
+            return;
+        }
+        self.divider = self.volume;
+        if self.decay > 0 {
+            self.decay -= 1;
+        } else if self.looping {
+            self.decay = 15;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant {
+            return self.volume;
+        }
+        return self.decay;
+    }
+}
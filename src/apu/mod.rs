@@ -0,0 +1,332 @@
+pub mod envelope;
+pub mod length;
+pub mod pulse;
+pub mod triangle;
+pub mod noise;
+pub mod dmc;
+
+use pulse::Pulse;
+use triangle::Triangle;
+use noise::Noise;
+use dmc::Dmc;
+
+// 2A03 APU
+//
+// 0x4000 - 0x4003: Pulse 1
+// 0x4004 - 0x4007: Pulse 2
+// 0x4008 - 0x400B: Triangle
+// 0x400C - 0x400F: Noise
+// 0x4010 - 0x4013: DMC
+// 0x4015: Status (read) / Channel enable (write)
+// 0x4017: Frame counter (write only, reads are the second joypad)
+pub const APU_REG_STATUS: u16 = 0x4015;
+pub const APU_REG_FRAME_COUNTER: u16 = 0x4017;
+
+pub const CPU_CLOCK_RATE: f64 = 1_789_773.0;
+pub const DEFAULT_SAMPLE_RATE: f64 = 44_100.0;
+
+// frame counter steps in CPU cycles (NTSC)
+const STEP1: usize = 7457;
+const STEP2: usize = 14913;
+const STEP3: usize = 22371;
+const STEP4: usize = 29829;
+const STEP5: usize = 37281;
+
+pub struct Apu {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    cycles: usize,
+    frame_cycles: usize,
+    five_step: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+
+    sample_rate: f64,
+    sample_clock: f64,
+    sample_sum: f32,
+    sample_count: u32,
+    samples: Vec<f32>,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Apu {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: Dmc::new(),
+            cycles: 0,
+            frame_cycles: 0,
+            five_step: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_clock: 0.0,
+            sample_sum: 0.0,
+            sample_count: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4000 ..= 0x4003 => self.pulse1.write(addr - 0x4000, data),
+            0x4004 ..= 0x4007 => self.pulse2.write(addr - 0x4004, data),
+            0x4008 ..= 0x400B => self.triangle.write(addr - 0x4008, data),
+            0x400C ..= 0x400F => self.noise.write(addr - 0x400C, data),
+            0x4010 ..= 0x4013 => self.dmc.write(addr - 0x4010, data),
+            APU_REG_STATUS => self.write_status(data),
+            APU_REG_FRAME_COUNTER => self.write_frame_counter(data),
+            _ => {},
+        }
+    }
+
+    // 7  bit  0
+    // ---- ----
+    // IF-D NT21
+    // || | ||||
+    // || | |||+- Pulse 1 length counter > 0
+    // || | ||+-- Pulse 2 length counter > 0
+    // || | |+--- Triangle length counter > 0
+    // || | +---- Noise length counter > 0
+    // || +------ DMC bytes remaining > 0
+    // |+-------- Frame interrupt (cleared by the read)
+    // +--------- DMC interrupt
+    pub fn read_status(&mut self) -> u8 {
+        let mut status = 0;
+        if self.pulse1.length.is_active() { status |= 0b0000_0001; }
+        if self.pulse2.length.is_active() { status |= 0b0000_0010; }
+        if self.triangle.length.is_active() { status |= 0b0000_0100; }
+        if self.noise.length.is_active() { status |= 0b0000_1000; }
+        if self.dmc.is_active() { status |= 0b0001_0000; }
+        if self.frame_irq { status |= 0b0100_0000; }
+        if self.dmc.irq { status |= 0b1000_0000; }
+        self.frame_irq = false;
+        return status;
+    }
+
+    fn write_status(&mut self, data: u8) {
+        self.pulse1.length.set_enabled(data & 0b0000_0001 != 0);
+        self.pulse2.length.set_enabled(data & 0b0000_0010 != 0);
+        self.triangle.length.set_enabled(data & 0b0000_0100 != 0);
+        self.noise.length.set_enabled(data & 0b0000_1000 != 0);
+        self.dmc.set_enabled(data & 0b0001_0000 != 0);
+    }
+
+    // MI-- ----: 5-step mode, IRQ inhibit
+    fn write_frame_counter(&mut self, data: u8) {
+        self.five_step = data & 0b1000_0000 != 0;
+        self.irq_inhibit = data & 0b0100_0000 != 0;
+        if self.irq_inhibit {
+            self.frame_irq = false;
+        }
+        self.frame_cycles = 0;
+        if self.five_step {
+            self.clock_quarter_frame();
+            self.clock_half_frame();
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.length.clock();
+        self.pulse2.length.clock();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+
+    fn clock_frame_counter(&mut self) {
+        self.frame_cycles += 1;
+        match (self.frame_cycles, self.five_step) {
+            (STEP1, _) | (STEP3, _) => {
+                self.clock_quarter_frame();
+            },
+            (STEP2, _) | (STEP5, true) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                if self.five_step && self.frame_cycles == STEP5 {
+                    self.frame_cycles = 0;
+                }
+            },
+            (STEP4, false) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                if !self.irq_inhibit {
+                    self.frame_irq = true;
+                }
+                self.frame_cycles = 0;
+            },
+            _ => {},
+        }
+    }
+
+    // advances one CPU cycle
+    pub fn tick(&mut self) {
+        self.cycles += 1;
+        self.clock_frame_counter();
+        if self.cycles.is_multiple_of(2) {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+
+        // average the output over each sample period
+        self.sample_sum += self.mix();
+        self.sample_count += 1;
+        self.sample_clock += self.sample_rate;
+        if self.sample_clock >= CPU_CLOCK_RATE {
+            self.sample_clock -= CPU_CLOCK_RATE;
+            self.samples.push(self.sample_sum / self.sample_count as f32);
+            self.sample_sum = 0.0;
+            self.sample_count = 0;
+        }
+    }
+
+    // non-linear mixer, 0.0 - 1.0
+    fn mix(&self) -> f32 {
+        let pulse = (self.pulse1.output() + self.pulse2.output()) as f32;
+        let pulse_out = if pulse == 0.0 { 0.0 } else { 95.88 / (8128.0 / pulse + 100.0) };
+
+        let tnd = self.triangle.output() as f32 / 8227.0
+                + self.noise.output() as f32 / 12241.0
+                + self.dmc.output() as f32 / 22638.0;
+        let tnd_out = if tnd == 0.0 { 0.0 } else { 159.79 / (1.0 / tnd + 100.0) };
+
+        return pulse_out + tnd_out;
+    }
+
+    // level of the APU interrupt line
+    pub fn irq(&self) -> bool {
+        return self.frame_irq || self.dmc.irq;
+    }
+
+    // the DMC wants a sample byte from this address
+    pub fn dmc_dma_address(&self) -> Option<u16> {
+        return self.dmc.dma_address();
+    }
+
+    pub fn dmc_fill(&mut self, data: u8) {
+        self.dmc.fill(data);
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        return std::mem::take(&mut self.samples);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(apu: &mut Apu, cycles: usize) {
+        for _ in 0 .. cycles {
+            apu.tick();
+        }
+    }
+
+    #[test]
+    fn test_length_counter_status() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4003, 0b0000_1000); // ignored while disabled
+        assert_eq!(apu.read_status() & 0x01, 0);
+
+        apu.write_register(APU_REG_STATUS, 0b0000_0001);
+        apu.write_register(0x4003, 0b0000_1000); // length 254
+        assert_eq!(apu.read_status() & 0x01, 0x01);
+
+        apu.write_register(APU_REG_STATUS, 0);
+        assert_eq!(apu.read_status() & 0x01, 0);
+    }
+
+    #[test]
+    fn test_length_counter_expires() {
+        let mut apu = Apu::new();
+        apu.write_register(APU_REG_STATUS, 0b0000_0100);
+        apu.write_register(0x400B, 0b0001_1000); // length 2
+        // two half frames in the 4-step sequence
+        run(&mut apu, STEP2);
+        assert_eq!(apu.read_status() & 0x04, 0x04);
+        run(&mut apu, STEP4 - STEP2);
+        assert_eq!(apu.read_status() & 0x04, 0);
+    }
+
+    #[test]
+    fn test_frame_irq() {
+        let mut apu = Apu::new();
+        run(&mut apu, STEP4);
+        assert!(apu.irq());
+        assert_eq!(apu.read_status() & 0x40, 0x40);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_frame_irq_inhibit_and_five_step() {
+        let mut apu = Apu::new();
+        apu.write_register(APU_REG_FRAME_COUNTER, 0b0100_0000);
+        run(&mut apu, STEP4);
+        assert!(!apu.irq());
+
+        apu.write_register(APU_REG_FRAME_COUNTER, 0b1000_0000);
+        run(&mut apu, STEP5);
+        assert!(!apu.irq());
+    }
+
+    #[test]
+    fn test_dmc_dma() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4010, 0b1000_0000); // IRQ enabled
+        apu.write_register(0x4012, 0x01); // $C040
+        apu.write_register(0x4013, 0x00); // 1 byte
+        apu.write_register(APU_REG_STATUS, 0b0001_0000);
+        assert_eq!(apu.read_status() & 0x10, 0x10);
+        assert_eq!(apu.dmc_dma_address(), Some(0xc040));
+
+        apu.dmc_fill(0xff);
+        assert_eq!(apu.dmc_dma_address(), None);
+        assert_eq!(apu.read_status() & 0x10, 0);
+        assert!(apu.irq());
+    }
+
+    #[test]
+    fn test_pulse_output() {
+        let mut apu = Apu::new();
+        apu.write_register(APU_REG_STATUS, 0b0000_0001);
+        apu.write_register(0x4000, 0b1011_1111); // 50% duty, constant volume 15
+        apu.write_register(0x4002, 0xfd);
+        apu.write_register(0x4003, 0b0000_1000);
+        run(&mut apu, 4096);
+        // the square wave swings by the full pulse volume
+        let samples = apu.take_samples();
+        let high = samples.iter().cloned().fold(f32::MIN, f32::max);
+        let low = samples.iter().cloned().fold(f32::MAX, f32::min);
+        assert!(high - low > 0.1);
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(48_000.0);
+        run(&mut apu, CPU_CLOCK_RATE as usize / 10);
+        let samples = apu.take_samples().len();
+        assert!((4799 ..= 4801).contains(&samples));
+    }
+}
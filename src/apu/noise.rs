@@ -0,0 +1,71 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length::LengthCounter;
+
+// noise channel, $400C - $400F
+//
+// $400C: --LC VVVV  envelope
+// $400E: M--- PPPP  mode, period
+// $400F: LLLL L---  length counter load
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+pub struct Noise {
+    shift: u16,
+    mode: bool,
+    timer: u16,
+    timer_period: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise {
+            shift: 1,
+            mode: false,
+            timer: 0,
+            timer_period: PERIOD_TABLE[0],
+            envelope: Envelope::new(),
+            length: LengthCounter::new(),
+        }
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.length.halt = data & 0b0010_0000 != 0;
+                self.envelope.write(data);
+            },
+            1 => {},
+            2 => {
+                self.mode = data & 0b1000_0000 != 0;
+                self.timer_period = PERIOD_TABLE[(data & 0b0000_1111) as usize];
+            },
+            _ => {
+                self.length.load(data);
+                self.envelope.restart();
+            },
+        }
+    }
+
+    // clocked every CPU cycle, the periods are in CPU cycles
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period - 1;
+        // mode 1 taps bit 6 for the short, metallic sequence
+        let tap = if self.mode { 6 } else { 1 };
+        let feedback = (self.shift ^ (self.shift >> tap)) & 0x01;
+        self.shift = (self.shift >> 1) | (feedback << 14);
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.is_active() || self.shift & 0x01 != 0 {
+            return 0;
+        }
+        return self.envelope.output();
+    }
+}
@@ -0,0 +1,123 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length::LengthCounter;
+
+// pulse channel, $4000 - $4003 and $4004 - $4007
+//
+// $4000: DDLC VVVV  duty, envelope
+// $4001: EPPP NSSS  sweep enable, period, negate, shift
+// $4002: TTTT TTTT  timer low
+// $4003: LLLL LTTT  length counter load, timer high
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+pub struct Pulse {
+    // pulse 1 negates with ones' complement, pulse 2 with two's complement
+    ones_complement: bool,
+    duty: u8,
+    sequence: u8,
+    timer: u16,
+    timer_period: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Pulse {
+            ones_complement: ones_complement,
+            duty: 0,
+            sequence: 0,
+            timer: 0,
+            timer_period: 0,
+            envelope: Envelope::new(),
+            length: LengthCounter::new(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.duty = data >> 6;
+                self.length.halt = data & 0b0010_0000 != 0;
+                self.envelope.write(data);
+            },
+            1 => {
+                self.sweep_enabled = data & 0b1000_0000 != 0;
+                self.sweep_period = (data >> 4) & 0b0111;
+                self.sweep_negate = data & 0b0000_1000 != 0;
+                self.sweep_shift = data & 0b0000_0111;
+                self.sweep_reload = true;
+            },
+            2 => {
+                self.timer_period = (self.timer_period & 0x0700) | data as u16;
+            },
+            _ => {
+                self.timer_period = (self.timer_period & 0x00ff) | (((data & 0b0111) as u16) << 8);
+                self.length.load(data);
+                self.sequence = 0;
+                self.envelope.restart();
+            },
+        }
+    }
+
+    // clocked every other CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence = (self.sequence + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if !self.sweep_negate {
+            return self.timer_period + change;
+        }
+        if self.ones_complement {
+            return self.timer_period.saturating_sub(change + 1);
+        }
+        return self.timer_period.saturating_sub(change);
+    }
+
+    fn is_muted(&self) -> bool {
+        return self.timer_period < 8 || self.sweep_target() > 0x07ff;
+    }
+
+    // clocked by the half frame
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.is_muted() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.is_active() || self.is_muted() || DUTY_TABLE[self.duty as usize][self.sequence as usize] == 0 {
+            return 0;
+        }
+        return self.envelope.output();
+    }
+}
@@ -0,0 +1,84 @@
+use crate::apu::length::LengthCounter;
+
+// triangle channel, $4008 - $400B
+//
+// $4008: CRRR RRRR  length counter halt / linear counter control, linear counter reload
+// $400A: TTTT TTTT  timer low
+// $400B: LLLL LTTT  length counter load, timer high
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10,  9,  8,  7,  6,  5,  4,  3,  2,  1,  0,
+     0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15,
+];
+
+pub struct Triangle {
+    sequence: u8,
+    timer: u16,
+    timer_period: u16,
+    pub length: LengthCounter,
+    control: bool,
+    linear_counter: u8,
+    linear_reload_value: u8,
+    linear_reload: bool,
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Triangle {
+            sequence: 0,
+            timer: 0,
+            timer_period: 0,
+            length: LengthCounter::new(),
+            control: false,
+            linear_counter: 0,
+            linear_reload_value: 0,
+            linear_reload: false,
+        }
+    }
+
+    pub fn write(&mut self, register: u16, data: u8) {
+        match register {
+            0 => {
+                self.control = data & 0b1000_0000 != 0;
+                self.length.halt = self.control;
+                self.linear_reload_value = data & 0b0111_1111;
+            },
+            1 => {},
+            2 => {
+                self.timer_period = (self.timer_period & 0x0700) | data as u16;
+            },
+            _ => {
+                self.timer_period = (self.timer_period & 0x00ff) | (((data & 0b0111) as u16) << 8);
+                self.length.load(data);
+                self.linear_reload = true;
+            },
+        }
+    }
+
+    // clocked every CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period;
+        if self.length.is_active() && self.linear_counter > 0 {
+            self.sequence = (self.sequence + 1) & 0x1f;
+        }
+    }
+
+    // clocked by the quarter frame
+    pub fn clock_linear(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        return SEQUENCE[self.sequence as usize];
+    }
+}
@@ -0,0 +1,193 @@
+// helpers to play the APU output on a host audio device
+//
+// the emulator produces samples at its own pace (one frame per vsync),
+// while the device consumes them at its fixed rate. samples are queued in
+// a ring buffer, and the resampling ratio is nudged to keep the buffer
+// around its target level, so audio neither underruns nor drifts.
+
+pub struct RingBuffer {
+    data: Vec<f32>,
+    read: usize,
+    len: usize,
+    last: f32,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer {
+            data: vec![0.0; capacity],
+            read: 0,
+            len: 0,
+            last: 0.0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.data.len();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    // samples which do not fit are dropped
+    pub fn push(&mut self, samples: &[f32]) {
+        for sample in samples {
+            if self.len == self.data.len() {
+                return;
+            }
+            let write = (self.read + self.len) % self.data.len();
+            self.data[write] = *sample;
+            self.len += 1;
+        }
+    }
+
+    // on underrun the last sample is repeated to avoid clicks
+    pub fn pop(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            if self.len > 0 {
+                self.last = self.data[self.read];
+                self.read = (self.read + 1) % self.data.len();
+                self.len -= 1;
+            }
+            *sample = self.last;
+        }
+    }
+}
+
+// adjusts the resampling ratio by at most max_adjust (e.g. 0.005 = 0.5%)
+pub struct RateControl {
+    target: usize,
+    max_adjust: f64,
+}
+
+impl RateControl {
+    pub fn new(target: usize, max_adjust: f64) -> Self {
+        RateControl {
+            target: target,
+            max_adjust: max_adjust,
+        }
+    }
+
+    // output samples per input sample, above 1.0 when the buffer runs low
+    pub fn ratio(&self, buffered: usize) -> f64 {
+        let error = (self.target as f64 - buffered as f64) / self.target as f64;
+        return 1.0 + self.max_adjust * error.clamp(-1.0, 1.0);
+    }
+}
+
+// linear interpolation resampler, keeps its phase across calls
+pub struct Resampler {
+    position: f64,
+    previous: f32,
+}
+
+impl Resampler {
+    pub fn new() -> Self {
+        Resampler {
+            position: 0.0,
+            previous: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: &[f32], ratio: f64) -> Vec<f32> {
+        let step = 1.0 / ratio;
+        let mut output = Vec::with_capacity((input.len() as f64 * ratio) as usize + 1);
+        // position is relative to the previous sample, 0.0 - 1.0 interpolates towards input[0]
+        while self.position < input.len() as f64 {
+            let index = self.position as usize;
+            let fraction = (self.position - index as f64) as f32;
+            let from = if index == 0 { self.previous } else { input[index - 1] };
+            let to = input[index];
+            output.push(from + (to - from) * fraction);
+            self.position += step;
+        }
+        self.position -= input.len() as f64;
+        if let Some(last) = input.last() {
+            self.previous = *last;
+        }
+        return output;
+    }
+}
+
+// removes the DC offset of the mixer output, which is 0.0 - 1.0
+pub struct HighPass {
+    previous_in: f32,
+    previous_out: f32,
+}
+
+impl HighPass {
+    pub fn new() -> Self {
+        HighPass {
+            previous_in: 0.0,
+            previous_out: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let out = sample - self.previous_in + 0.996 * self.previous_out;
+        self.previous_in = sample;
+        self.previous_out = out;
+        return out;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut buffer = RingBuffer::new(4);
+        buffer.push(&[1.0, 2.0, 3.0]);
+        let mut out = [0.0; 2];
+        buffer.pop(&mut out);
+        assert_eq!(out, [1.0, 2.0]);
+
+        // wraps around, and drops what does not fit
+        buffer.push(&[4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(buffer.len(), 4);
+        let mut out = [0.0; 6];
+        buffer.pop(&mut out);
+        assert_eq!(out, [3.0, 4.0, 5.0, 6.0, 6.0, 6.0]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_rate_control() {
+        let control = RateControl::new(1000, 0.005);
+        assert_eq!(control.ratio(1000), 1.0);
+        assert!(control.ratio(500) > 1.0);
+        assert!(control.ratio(1500) < 1.0);
+        assert_eq!(control.ratio(0), 1.005);
+        assert_eq!(control.ratio(5000), 0.995);
+    }
+
+    #[test]
+    fn test_resampler() {
+        let mut resampler = Resampler::new();
+        let input = vec![1.0; 1000];
+        assert_eq!(resampler.process(&input, 1.0).len(), 1000);
+
+        // the fractional phase carries over between calls
+        let mut total = 0;
+        for _ in 0 .. 10 {
+            total += resampler.process(&input, 1.01).len();
+        }
+        assert!((10099 ..= 10101).contains(&total));
+    }
+
+    #[test]
+    fn test_high_pass() {
+        let mut filter = HighPass::new();
+        let mut out = 1.0;
+        for _ in 0 .. 10000 {
+            out = filter.process(0.5);
+        }
+        assert!(out.abs() < 0.001);
+    }
+}
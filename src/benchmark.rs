@@ -0,0 +1,52 @@
+use nesemulator::Nes;
+
+use std::env;
+use std::time::Instant;
+use getopts::Options;
+
+// NTSC frame rate
+const NES_FPS: f64 = 60.0988;
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} <iNES ROM> [options]", program);
+    print!("{}", opts.usage(&brief));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let mut opts = Options::new();
+    opts.optopt("f", "frames", "frames to run (default: 600)", "FRAMES");
+    opts.optflag("h", "help", "print this help menu");
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => { m },
+        Err(f) => { panic!("{}",f.to_string()); },
+    };
+    if matches.opt_present("h") || matches.free.is_empty() {
+        print_usage(&program, opts);
+        return;
+    }
+    let frames = match matches.opt_str("f") {
+        Some(f) => f.parse::<usize>().expect("invalid frames."),
+        None => 600,
+    };
+    let raw = std::fs::read(&matches.free[0]).expect("unable to read.");
+
+    let mut nes = Nes::new();
+    nes.load_rom(&raw).expect("failed to load ROM.");
+
+    let start = Instant::now();
+    let mut done = 0;
+    while done < frames {
+        if !nes.run_frame() {
+            println!("CPU stopped at ${:04X}", nes.cpu().reg_pc);
+            break;
+        }
+        done += 1;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let fps = done as f64 / elapsed;
+    println!("{} frames in {:.3}s: {:.1} fps ({:.2}x realtime)", done, elapsed, fps, fps / NES_FPS);
+}
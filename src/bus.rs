@@ -1,6 +1,11 @@
 use crate::rom::*;
 use crate::ppu::*;
+use crate::apu::*;
 use crate::joypad::*;
+use crate::mapper::*;
+use crate::mapper::nrom::Nrom;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 // memory map
 //
@@ -17,26 +22,42 @@ use crate::joypad::*;
 // 0x6000 - 0x7FFF: SRAM
 // 0x8000 - 0xFFFF: ROM
 // => 0xFFFC - 0xFFFD: Start Vector
+// (0x4020 - 0xFFFF is handled by the cartridge mapper, except SRAM which is on the bus)
 
 const RAM: u16      = 0x0000;
 const RAM_END: u16  = 0x1FFF;
+#[allow(dead_code)]
 const PPU: u16      = 0x2000;
 const PPU_END: u16  = 0x3FFF;
 const APU: u16      = 0x4000;
 const APU_END: u16  = 0x4015;
 const JOYPAD_1: u16 = 0x4016;
 const JOYPAD_2: u16 = 0x4017;
-const ROM: u16      = 0x8000;
-const ROM_END: u16  = 0xFFFF;
+const PRG_RAM: u16     = 0x6000;
+const PRG_RAM_END: u16 = 0x7FFF;
+const CARTRIDGE: u16     = 0x4020;
+const CARTRIDGE_END: u16 = 0xFFFF;
 
-pub struct Bus<'call> {
+pub struct Bus {
     cpu_vram: [u8; 2048],
-    prg_rom: Vec<u8>,
+    mapper: SharedMapper,
     ppu: NesPPU,
+    apu: Apu,
     joypad_1: Joypad,
+    prg_ram: Vec<u8>,
+    battery: bool,
+    prg_ram_dirty: bool,
 
     cycles: usize,
-    vsync_callback: Box<dyn FnMut(&NesPPU, &mut Joypad) + 'call>,
+    frame_complete: bool,
+    access_log: Option<Vec<BusAccess>>,
+}
+
+// a single CPU bus cycle, as recorded by the access log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusAccess {
+    Read(u16, u8),
+    Write(u16, u8),
 }
 
 pub trait Memory {
@@ -53,56 +74,169 @@ pub trait Memory {
         self.memory_write_u8(addr, lo);
         self.memory_write_u8(addr + 1, hi);
     }
+
+    // runs the rest of the system for the given CPU cycles
+    fn tick(&mut self, _cycles: u8) {}
+
+    // a CPU read cycle: the access happens, then the rest of the system catches up
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let data = self.memory_read_u8(addr);
+        self.tick(1);
+        return data;
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        self.memory_write_u8(addr, data);
+        self.tick(1);
+    }
+
+    // true once per NMI edge
+    fn poll_nmi(&mut self) -> bool {
+        return false;
+    }
+
+    // level of the maskable interrupt line
+    fn poll_irq(&self) -> bool {
+        return false;
+    }
 }
 
-impl<'a> Bus<'a> {
-    pub fn new_with_rom<'call, F>(rom: Rom, vsync_callback: F) -> Bus<'call>
-    where
-        F: FnMut(&NesPPU, &mut Joypad) + 'call,
-    {
-        let ppu = NesPPU::new(rom.chr_rom, rom.screen_mirroring);
-        Bus {
-            cpu_vram: [0; 2048],
-            prg_rom: rom.prg_rom,
-            ppu: ppu,
-            joypad_1: Joypad::new(),
+// 64KB of plain RAM, for running the CPU outside the NES
+pub struct FlatRam {
+    data: Vec<u8>,
+    cycles: usize,
+}
+
+impl FlatRam {
+    pub fn new() -> Self {
+        FlatRam {
+            data: vec![0; 0x10000],
             cycles: 0,
-            vsync_callback: Box::from(vsync_callback),
         }
     }
 
-    pub fn new<'call, F>(vsync_callback: F) -> Bus<'call>
-    where
-        F: FnMut(&NesPPU, &mut Joypad) + 'call,
-    {
+    pub fn load(&mut self, addr: u16, data: &[u8]) {
+        let start = addr as usize;
+        let len = data.len().min(self.data.len() - start);
+        self.data[start .. start + len].copy_from_slice(&data[.. len]);
+    }
+
+    // CPU cycles since power on
+    pub fn cycles(&self) -> usize {
+        return self.cycles;
+    }
+}
+
+impl Memory for FlatRam {
+    fn memory_read_u8(&mut self, addr: u16) -> u8 {
+        return self.data[addr as usize];
+    }
+
+    fn memory_write_u8(&mut self, addr: u16, data: u8) {
+        self.data[addr as usize] = data;
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        self.cycles += cycles as usize;
+    }
+}
+
+impl Bus {
+    pub fn new_with_rom(rom: Rom) -> Result<Self, String> {
+        let prg_ram_size = rom.prg_ram_size + rom.prg_nvram_size;
+        let battery = rom.battery;
+        let mapper = new_mapper(rom)?;
+        let mut bus = Bus::new_with_mapper(mapper);
+        bus.prg_ram = vec![0; prg_ram_size];
+        bus.battery = battery;
+        return Ok(bus);
+    }
+
+    pub fn new() -> Self {
+        let mapper = Rc::new(RefCell::new(Nrom::new(vec![0; 16384], Chr::new(vec![0; 8192]), Mirroring::HORIZONTAL)));
+        return Bus::new_with_mapper(mapper);
+    }
+
+    pub fn new_with_mapper(mapper: SharedMapper) -> Self {
+        let ppu = NesPPU::new_with_mapper(mapper.clone());
         Bus {
             cpu_vram: [0; 2048],
-            prg_rom: [0; 16384].to_vec(),
-            ppu: NesPPU::new_empty_rom(),
+            mapper: mapper,
+            ppu: ppu,
+            apu: Apu::new(),
             joypad_1: Joypad::new(),
+            prg_ram: vec![0; 0x2000],
+            battery: false,
+            prg_ram_dirty: false,
             cycles: 0,
-            vsync_callback: Box::from(vsync_callback),
+            frame_complete: false,
+            access_log: None,
         }
     }
 
-    pub fn tick(&mut self, cycles: u8) {
-        self.cycles += cycles as usize;
+    // records every CPU bus cycle from now on
+    pub fn enable_access_log(&mut self) {
+        self.access_log = Some(Vec::new());
+    }
 
-        let nmi_before = self.ppu.nmi_interrupt.is_some();
-        self.ppu.tick(cycles * 3); // PPU cycles are 3 times of CPU cycles
-        let nmi_after = self.ppu.nmi_interrupt.is_some();
+    pub fn take_access_log(&mut self) -> Vec<BusAccess> {
+        return self.access_log.as_mut().map(std::mem::take).unwrap_or_default();
+    }
 
-        if !nmi_before && nmi_after {
-            (self.vsync_callback)(&self.ppu, &mut self.joypad_1);
-        }
+    // CPU cycles since power on
+    pub fn cycles(&self) -> usize {
+        return self.cycles;
+    }
+
+    // true once per frame, when the PPU enters vblank
+    pub fn poll_frame_complete(&mut self) -> bool {
+        return std::mem::take(&mut self.frame_complete);
+    }
+
+    pub fn ppu(&self) -> &NesPPU {
+        return &self.ppu;
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut NesPPU {
+        return &mut self.ppu;
+    }
+
+    pub fn cpu_ram(&self) -> &[u8] {
+        return &self.cpu_vram;
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        return &mut self.apu;
+    }
+
+    pub fn joypad_1(&mut self) -> &mut Joypad {
+        return &mut self.joypad_1;
+    }
+
+    pub fn has_battery(&self) -> bool {
+        return self.battery;
     }
 
-    pub fn poll_nmi(&mut self) -> Option<u8> {
-        return self.ppu.poll_nmi();
+    pub fn prg_ram(&self) -> &[u8] {
+        return &self.prg_ram;
+    }
+
+    pub fn load_prg_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.prg_ram.len());
+        self.prg_ram[.. len].copy_from_slice(&data[.. len]);
+    }
+
+    // true once after the battery backed RAM has been written
+    pub fn poll_prg_ram_dirty(&mut self) -> bool {
+        return std::mem::take(&mut self.prg_ram_dirty);
+    }
+
+    fn prg_ram_index(&self, addr: u16) -> usize {
+        return (addr - PRG_RAM) as usize % self.prg_ram.len();
     }
 }
 
-impl Memory for Bus<'_> {
+impl Memory for Bus {
     fn memory_read_u8(&mut self, addr: u16) -> u8 {
         match addr {
             RAM ..= RAM_END => {
@@ -131,8 +265,11 @@ impl Memory for Bus<'_> {
                 let fixed_addr = addr & 0x2007;
                 return self.memory_read_u8(fixed_addr);
             },
+            APU_REG_STATUS => {
+                return self.apu.read_status();
+            },
             APU ..= APU_END => {
-                // TODO
+                // write-only
                 return 0;
             },
             JOYPAD_1 => {
@@ -142,12 +279,15 @@ impl Memory for Bus<'_> {
                 // TODO
                 return 0;
             },
-            ROM ..= ROM_END => {
-                let mut fixed_addr = addr - 0x8000;
-                if self.prg_rom.len() == 0x4000 {
-                    fixed_addr = fixed_addr & 0x3FFF;
+            PRG_RAM ..= PRG_RAM_END => {
+                if self.prg_ram.is_empty() || !self.mapper.borrow().prg_ram_enabled() {
+                    // open bus
+                    return 0;
                 }
-                return self.prg_rom[fixed_addr as usize];
+                return self.prg_ram[self.prg_ram_index(addr)];
+            },
+            CARTRIDGE ..= CARTRIDGE_END => {
+                return self.mapper.borrow_mut().cpu_read(addr);
             },
             _ => {
                 println!("invalid read at {:04x}",addr);
@@ -193,22 +333,42 @@ impl Memory for Bus<'_> {
                     buffer[i as usize] = self.memory_read_u8(hi + i);
                 }
                 self.ppu.write_oam_dma(&buffer);
+                // the CPU is halted during the copy, plus an alignment cycle on odd cycles
+                let stall = if self.cycles.is_multiple_of(2) { 513 } else { 514 };
+                for _ in 0 .. stall {
+                    self.tick(1);
+                }
             },
             PPU_REG_END ..= PPU_END => {
                 let fixed_addr = addr & 0x2007;
                 self.memory_write_u8(fixed_addr, data);
             },
             APU ..= APU_END => {
-                // TODO
+                self.apu.write_register(addr, data);
             },
             JOYPAD_1 => {
                 self.joypad_1.write(data);
             },
-            JOYPAD_2 => {
-                // TODO
+            APU_REG_FRAME_COUNTER => {
+                self.apu.write_register(addr, data);
+            },
+            PRG_RAM ..= PRG_RAM_END => {
+                let mapper = self.mapper.borrow();
+                if !self.prg_ram.is_empty() && mapper.prg_ram_enabled() && mapper.prg_ram_writable() {
+                    let index = self.prg_ram_index(addr);
+                    self.prg_ram[index] = data;
+                    self.prg_ram_dirty = self.battery;
+                }
+                drop(mapper);
+                // some boards have registers in this range
+                self.mapper.borrow_mut().cpu_write(addr, data);
             },
-            ROM ..= ROM_END => {
-                panic!("invalid write to ROM at {:04x}",addr);
+            CARTRIDGE ..= CARTRIDGE_END => {
+                let mut mapper = self.mapper.borrow_mut();
+                mapper.set_cpu_cycle(self.cycles);
+                mapper.cpu_write(addr, data);
+                drop(mapper);
+                self.ppu.mirroring = self.mapper.borrow().mirroring();
             },
             _ => {
                 println!("invalid write at {:04x}",addr);
@@ -216,4 +376,170 @@ impl Memory for Bus<'_> {
         }
     }
 
-}
\ No newline at end of file
+    fn tick(&mut self, cycles: u8) {
+        self.cycles += cycles as usize;
+        if self.ppu.tick(cycles * 3) { // PPU cycles are 3 times of CPU cycles
+            self.frame_complete = true;
+        }
+        let mut stall = 0;
+        for _ in 0 .. cycles {
+            self.apu.tick();
+            // DMC sample fetch steals the bus from the CPU
+            if let Some(addr) = self.apu.dmc_dma_address() {
+                let data = self.memory_read_u8(addr);
+                self.apu.dmc_fill(data);
+                stall += 4;
+            }
+        }
+        if stall > 0 {
+            self.tick(stall);
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let data = self.memory_read_u8(addr);
+        if let Some(log) = self.access_log.as_mut() {
+            log.push(BusAccess::Read(addr, data));
+        }
+        self.tick(1);
+        return data;
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if let Some(log) = self.access_log.as_mut() {
+            log.push(BusAccess::Write(addr, data));
+        }
+        self.memory_write_u8(addr, data);
+        self.tick(1);
+    }
+
+    fn poll_nmi(&mut self) -> bool {
+        return self.ppu.poll_nmi().is_some();
+    }
+
+    fn poll_irq(&self) -> bool {
+        return self.mapper.borrow().irq() || self.apu.irq();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test::test_rom;
+
+    #[test]
+    fn test_mapper_mirroring_to_ppu() {
+        let rom = Rom {
+            prg_rom: vec![0; 0x8000],
+            chr_rom: vec![0; 0x2000],
+            mapper: 1,
+            screen_mirroring: Mirroring::HORIZONTAL,
+            ..Rom::new_empty_rom().unwrap()
+        };
+        let mut bus = Bus::new_with_rom(rom).unwrap();
+        // MMC1 control = 0b0_11_10 (vertical)
+        for bit in [0, 1, 1, 1, 0] {
+            bus.memory_write_u8(0x8000, bit);
+        }
+        assert_eq!(bus.ppu().mirroring, Mirroring::VERTICAL);
+    }
+
+    #[test]
+    fn test_dmc_dma_steals_cycles() {
+        let mut bus = Bus::new();
+        bus.memory_write_u8(0x4013, 0x00); // 1 byte sample
+        bus.memory_write_u8(0x4015, 0b0001_0000);
+        bus.tick(2);
+        assert_eq!(bus.cycles, 2 + 4);
+        assert_eq!(bus.memory_read_u8(0x4015) & 0x10, 0);
+    }
+
+    #[test]
+    fn test_oam_dma_stalls_cpu() {
+        let mut bus = Bus::new();
+        bus.cpu_write(0x4014, 0x02);
+        assert_eq!(bus.cycles, 1 + 513);
+        // one more cycle when the write lands on an odd cycle
+        bus.cpu_read(0x0000);
+        bus.cpu_write(0x4014, 0x02);
+        assert_eq!(bus.cycles, (1 + 513) + 1 + (1 + 514));
+    }
+
+    #[test]
+    fn test_flat_ram() {
+        let mut ram = FlatRam::new();
+        ram.load(0xfffe, &[0x11, 0x22, 0x33]);
+        assert_eq!(ram.memory_read_u16(0xfffe), 0x2211);
+        assert_eq!(ram.memory_read_u8(0x0000), 0x00);
+        assert_eq!(ram.cpu_read(0x2002), 0x00);
+        ram.cpu_write(0x4014, 0x02);
+        assert_eq!(ram.memory_read_u8(0x4014), 0x02);
+        assert_eq!(ram.cycles(), 2);
+    }
+
+    #[test]
+    fn test_access_log() {
+        let mut bus = Bus::new();
+        bus.cpu_write(0x0010, 0x55);
+        bus.enable_access_log();
+        bus.cpu_read(0x0010);
+        bus.cpu_write(0x0011, 0x66);
+        assert_eq!(bus.take_access_log(), vec![BusAccess::Read(0x0010, 0x55), BusAccess::Write(0x0011, 0x66)]);
+        assert_eq!(bus.take_access_log(), vec![]);
+        assert_eq!(bus.cycles, 3);
+    }
+
+    #[test]
+    fn test_apu_frame_irq() {
+        let mut bus = Bus::new();
+        assert!(!bus.poll_irq());
+        for _ in 0 .. 29830 / 2 {
+            bus.tick(2);
+        }
+        assert!(bus.poll_irq());
+        bus.memory_read_u8(0x4015);
+        assert!(!bus.poll_irq());
+    }
+
+    #[test]
+    fn test_prg_ram() {
+        let mut bus = Bus::new();
+        bus.memory_write_u8(0x6000, 0x55);
+        bus.memory_write_u8(0x7fff, 0x66);
+        assert_eq!(bus.memory_read_u8(0x6000), 0x55);
+        assert_eq!(bus.memory_read_u8(0x7fff), 0x66);
+        assert!(!bus.poll_prg_ram_dirty());
+    }
+
+    #[test]
+    fn test_battery_prg_ram() {
+        let mut rom = test_rom();
+        rom.battery = true;
+        let mut bus = Bus::new_with_rom(rom).unwrap();
+        assert!(bus.has_battery());
+        bus.load_prg_ram(&[1, 2, 3]);
+        assert_eq!(bus.memory_read_u8(0x6002), 3);
+        bus.memory_write_u8(0x6001, 0x55);
+        assert!(bus.poll_prg_ram_dirty());
+        assert!(!bus.poll_prg_ram_dirty());
+        assert_eq!(&bus.prg_ram()[.. 3], &[1, 0x55, 3]);
+    }
+
+    #[test]
+    fn test_nina_001_without_prg_ram() {
+        // NES 2.0, mapper 34 submapper 1, 64KB PRG ROM, 32KB CHR ROM, no PRG RAM
+        let mut raw = vec![0x4e, 0x45, 0x53, 0x1a, 0x04, 0x04, 0x20, 0x28, 0x10, 0, 0, 0, 0, 0, 0, 0];
+        let mut prg_rom = vec![0; 0x10000];
+        prg_rom[0x8000] = 1;
+        raw.extend(prg_rom);
+        raw.extend(vec![0; 0x8000]);
+        let rom = Rom::new(&raw).unwrap();
+        assert_eq!(rom.prg_ram_size + rom.prg_nvram_size, 0);
+
+        let mut bus = Bus::new_with_rom(rom).unwrap();
+        // PRG bank register at 0x7FFD
+        bus.memory_write_u8(0x7ffd, 1);
+        assert_eq!(bus.memory_read_u8(0x8000), 1);
+        assert_eq!(bus.memory_read_u8(0x7ffd), 0);
+    }
+}
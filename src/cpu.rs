@@ -1,9 +1,7 @@
 use bitflags::*;
-use std::collections::HashMap;
 use crate::opcodes;
 use crate::bus::Memory;
 use crate::bus::Bus;
-use crate::joypad::Joypad;
 
 bitflags! {
     #[repr(transparent)]
@@ -19,7 +17,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
     Immediate,
@@ -34,21 +32,44 @@ pub enum AddressingMode {
     NonAddressing,
 }
 
+// indexed writes and read-modify-writes always take the page fix-up cycle
+#[derive(PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+// executes an instruction after its opcode has been fetched
+pub type Handler<M> = fn(&mut CPU<M>, &AddressingMode);
+
 // memory map
 const STACK_BASE: u16 = 0x0100;
 const STACK_RESET: u8 = 0xfd;
 
-pub struct CPU<'a> {
+pub const DEFAULT_MAGIC_CONSTANT: u8 = 0xee;
+
+fn page_crossed(addr1: u16, addr2: u16) -> bool {
+    return addr1 & 0xFF00 != addr2 & 0xFF00;
+}
+
+pub struct CPU<M = Bus> {
     pub reg_a: u8,
     pub reg_x: u8,
     pub reg_y: u8,
     pub reg_sp: u8,
     pub status: CpuFlags,
     pub reg_pc: u16,
-    pub bus: Bus<'a>,
+    pub bus: M,
+    // treat BRK as the end of the program instead of a software interrupt (test programs)
+    pub stop_on_brk: bool,
+    stopped: bool,
+    // halted by a JAM opcode until the next reset
+    jammed: bool,
+    // chip dependent constant of the unstable XAA and LXA opcodes
+    pub magic_constant: u8,
 }
 
-impl Memory for CPU<'_> {
+impl<M: Memory> Memory for CPU<M> {
     fn memory_read_u8(&mut self, addr: u16) -> u8 {
         return self.bus.memory_read_u8(addr);
     }
@@ -60,8 +81,11 @@ impl Memory for CPU<'_> {
 
 mod interrupt {
     #[derive(PartialEq, Eq)]
+    #[allow(clippy::upper_case_acronyms)]
     pub enum InterruptType {
         NMI,
+        IRQ,
+        BRK,
     }
 
     #[derive(PartialEq, Eq)]
@@ -69,19 +93,107 @@ mod interrupt {
         pub(super) interrupt_type: InterruptType,
         pub(super) vector_address: u16,
         pub(super) break_flag_mask: u8,
-        pub(super) cpu_cycles: u8,
     }
 
     pub(super) const NMI: Interrupt = Interrupt {
         interrupt_type: InterruptType::NMI,
         vector_address: 0xfffa,
         break_flag_mask: 0b0010_0000,
-        cpu_cycles: 2,
+    };
+
+    pub(super) const IRQ: Interrupt = Interrupt {
+        interrupt_type: InterruptType::IRQ,
+        vector_address: 0xfffe,
+        break_flag_mask: 0b0010_0000,
+    };
+
+    pub(super) const BRK: Interrupt = Interrupt {
+        interrupt_type: InterruptType::BRK,
+        vector_address: 0xfffe,
+        break_flag_mask: 0b0011_0000,
     };
 }
 
-impl<'a> CPU<'a> {
-    pub fn new(bus: Bus<'a>) -> Self {
+// builds CPU::HANDLERS, and for the tests the names of the handlers,
+// so that they can be checked against opcodes::OPCODE_TABLE
+macro_rules! handler_table {
+    ($($handler:ident),* $(,)?) => {
+        const HANDLERS: [Handler<M>; 256] = [$(CPU::$handler),*];
+        #[cfg(test)]
+        const HANDLER_NAMES: [&'static str; 256] = [$(stringify!($handler)),*];
+    };
+}
+
+impl<M: Memory> CPU<M> {
+    // instruction handlers indexed by the opcode byte, see opcodes::CPU_OPCODES
+    handler_table! {
+        // 0x00
+        brk, ora, jam_unofficial, slo_unofficial, nop_with_read_unofficial, ora, asl, slo_unofficial,
+        // 0x08
+        php, ora, asl_accumulator, anc_unofficial, nop_with_read_unofficial, ora, asl, slo_unofficial,
+        // 0x10
+        bpl, ora, jam_unofficial, slo_unofficial, nop_with_read_unofficial, ora, asl, slo_unofficial,
+        // 0x18
+        clc, ora, nop_unofficial, slo_unofficial, nop_with_read_unofficial, ora, asl, slo_unofficial,
+        // 0x20
+        jsr, and, jam_unofficial, rla_unofficial, bit, and, rol, rla_unofficial,
+        // 0x28
+        plp, and, rol_accumulator, anc_unofficial, bit, and, rol, rla_unofficial,
+        // 0x30
+        bmi, and, jam_unofficial, rla_unofficial, nop_with_read_unofficial, and, rol, rla_unofficial,
+        // 0x38
+        sec, and, nop_unofficial, rla_unofficial, nop_with_read_unofficial, and, rol, rla_unofficial,
+        // 0x40
+        rti, eor, jam_unofficial, sre_unofficial, nop_with_read_unofficial, eor, lsr, sre_unofficial,
+        // 0x48
+        pha, eor, lsr_accumulator, alr_unofficial, jmp, eor, lsr, sre_unofficial,
+        // 0x50
+        bvc, eor, jam_unofficial, sre_unofficial, nop_with_read_unofficial, eor, lsr, sre_unofficial,
+        // 0x58
+        cli, eor, nop_unofficial, sre_unofficial, nop_with_read_unofficial, eor, lsr, sre_unofficial,
+        // 0x60
+        rts, adc, jam_unofficial, rra_unofficial, nop_with_read_unofficial, adc, ror, rra_unofficial,
+        // 0x68
+        pla, adc, ror_accumulator, arr_unofficial, jmp_indirect, adc, ror, rra_unofficial,
+        // 0x70
+        bvs, adc, jam_unofficial, rra_unofficial, nop_with_read_unofficial, adc, ror, rra_unofficial,
+        // 0x78
+        sei, adc, nop_unofficial, rra_unofficial, nop_with_read_unofficial, adc, ror, rra_unofficial,
+        // 0x80
+        nop_with_read_unofficial, sta, nop_with_read_unofficial, sax_unofficial, sty, sta, stx, sax_unofficial,
+        // 0x88
+        dey, nop_with_read_unofficial, txa, xaa_unofficial, sty, sta, stx, sax_unofficial,
+        // 0x90
+        bcc, sta, jam_unofficial, sha_unofficial, sty, sta, stx, sax_unofficial,
+        // 0x98
+        tya, sta, txs, tas_unofficial, shy_unofficial, sta, shx_unofficial, sha_unofficial,
+        // 0xa0
+        ldy, lda, ldx, lax_unofficial, ldy, lda, ldx, lax_unofficial,
+        // 0xa8
+        tay, lda, tax, lxa_unofficial, ldy, lda, ldx, lax_unofficial,
+        // 0xb0
+        bcs, lda, jam_unofficial, lax_unofficial, ldy, lda, ldx, lax_unofficial,
+        // 0xb8
+        clv, lda, tsx, las_unofficial, ldy, lda, ldx, lax_unofficial,
+        // 0xc0
+        cpy, cmp, nop_with_read_unofficial, dcp_unofficial, cpy, cmp, dec, dcp_unofficial,
+        // 0xc8
+        iny, cmp, dex, axs_unofficial, cpy, cmp, dec, dcp_unofficial,
+        // 0xd0
+        bne, cmp, jam_unofficial, dcp_unofficial, nop_with_read_unofficial, cmp, dec, dcp_unofficial,
+        // 0xd8
+        cld, cmp, nop_unofficial, dcp_unofficial, nop_with_read_unofficial, cmp, dec, dcp_unofficial,
+        // 0xe0
+        cpx, sbc, nop_with_read_unofficial, isb_unofficial, cpx, sbc, inc, isb_unofficial,
+        // 0xe8
+        inx, sbc, nop, sbc_unofficial, cpx, sbc, inc, isb_unofficial,
+        // 0xf0
+        beq, sbc, jam_unofficial, isb_unofficial, nop_with_read_unofficial, sbc, inc, isb_unofficial,
+        // 0xf8
+        sed, sbc, nop_unofficial, isb_unofficial, nop_with_read_unofficial, sbc, inc, isb_unofficial,
+    }
+
+    pub fn new(bus: M) -> Self {
         CPU {
             reg_a: 0,
             reg_x: 0,
@@ -90,6 +202,10 @@ impl<'a> CPU<'a> {
             status: CpuFlags::INTERRUPT_DISABLE | CpuFlags::BREAK2,
             reg_pc: 0,
             bus: bus,
+            stop_on_brk: false,
+            stopped: false,
+            jammed: false,
+            magic_constant: DEFAULT_MAGIC_CONSTANT,
         }
     }
 
@@ -121,7 +237,7 @@ impl<'a> CPU<'a> {
             },
             AddressingMode::Indirect_X => {
                 let base = self.bus.memory_read_u8(addr);
-                let ptr = (base as u8).wrapping_add(self.reg_x);
+                let ptr = base.wrapping_add(self.reg_x);
                 let lo = self.bus.memory_read_u8(ptr as u16);
                 let hi = self.bus.memory_read_u8(ptr.wrapping_add(1) as u16);
                 return (hi as u16) << 8 | (lo as u16)
@@ -129,7 +245,7 @@ impl<'a> CPU<'a> {
             AddressingMode::Indirect_Y => {
                 let base = self.bus.memory_read_u8(addr);
                 let lo = self.bus.memory_read_u8(base as u16);
-                let hi = self.bus.memory_read_u8((base as u8).wrapping_add(1) as u16);
+                let hi = self.bus.memory_read_u8(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.reg_y as u16);
                 return deref;
@@ -140,488 +256,607 @@ impl<'a> CPU<'a> {
         }
     }
 
-    fn get_operand_address(&mut self, mode: &AddressingMode) -> u16 {
+    // one bus cycle: every cycle of the 6502 is either a read or a write
+    fn read(&mut self, addr: u16) -> u8 {
+        return self.bus.cpu_read(addr);
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.bus.cpu_write(addr, data);
+    }
+
+    fn fetch(&mut self) -> u8 {
+        let data = self.read(self.reg_pc);
+        self.reg_pc = self.reg_pc.wrapping_add(1);
+        return data;
+    }
+
+    fn fetch_u16(&mut self) -> u16 {
+        let lo = self.fetch() as u16;
+        let hi = self.fetch() as u16;
+        return hi << 8 | lo;
+    }
+
+    // fetches the operand bytes and performs the dummy reads of the addressing mode
+    fn fetch_operand_address(&mut self, mode: &AddressingMode, access: Access) -> u16 {
         match mode {
-            AddressingMode::Immediate => self.reg_pc,
-            _ => self.get_absolute_address(mode, self.reg_pc),
+            AddressingMode::Immediate => {
+                let addr = self.reg_pc;
+                self.reg_pc = self.reg_pc.wrapping_add(1);
+                return addr;
+            },
+            AddressingMode::ZeroPage => self.fetch() as u16,
+            AddressingMode::Absolute => self.fetch_u16(),
+
+            AddressingMode::ZeroPage_X => {
+                let pos = self.fetch();
+                self.read(pos as u16);
+                return pos.wrapping_add(self.reg_x) as u16;
+            },
+            AddressingMode::ZeroPage_Y => {
+                let pos = self.fetch();
+                self.read(pos as u16);
+                return pos.wrapping_add(self.reg_y) as u16;
+            },
+            AddressingMode::Absolute_X => {
+                let base = self.fetch_u16();
+                return self.index_address(base, self.reg_x, access);
+            },
+            AddressingMode::Absolute_Y => {
+                let base = self.fetch_u16();
+                return self.index_address(base, self.reg_y, access);
+            },
+            AddressingMode::Indirect_X => {
+                let base = self.fetch();
+                self.read(base as u16);
+                let ptr = base.wrapping_add(self.reg_x);
+                let lo = self.read(ptr as u16);
+                let hi = self.read(ptr.wrapping_add(1) as u16);
+                return (hi as u16) << 8 | (lo as u16);
+            },
+            AddressingMode::Indirect_Y => {
+                let base = self.fetch();
+                let lo = self.read(base as u16);
+                let hi = self.read(base.wrapping_add(1) as u16);
+                let deref_base = (hi as u16) << 8 | (lo as u16);
+                return self.index_address(deref_base, self.reg_y, access);
+            },
+            _ => {
+                panic!("mode {:?} is not supported", mode);
+            }
         }
     }
 
-    fn adc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.bus.memory_read_u8(addr);
+    fn index_address(&mut self, base: u16, index: u8, access: Access) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        // the index is added to the low byte first, so the CPU reads from the
+        // unfixed address when the page is crossed, and always before a write
+        if access == Access::Write || page_crossed(base, addr) {
+            self.read((base & 0xFF00) | (addr & 0x00FF));
+        }
+        return addr;
+    }
+
+    fn read_operand(&mut self, mode: &AddressingMode) -> u8 {
+        let addr = self.fetch_operand_address(mode, Access::Read);
+        return self.read(addr);
+    }
+
+    fn write_operand(&mut self, mode: &AddressingMode, data: u8) {
+        let addr = self.fetch_operand_address(mode, Access::Write);
+        self.write(addr, data);
+    }
+
+    // the unmodified value is written back while the ALU works on it
+    fn read_modify_write<F>(&mut self, mode: &AddressingMode, modify: F) -> u8
+    where
+        F: FnOnce(&mut CPU<M>, u8) -> u8,
+    {
+        let addr = self.fetch_operand_address(mode, Access::Write);
+        let value = self.read(addr);
+        self.write(addr, value);
+        let result = modify(self, value);
+        self.write(addr, result);
+        return result;
+    }
+
+    pub(crate) fn adc(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.add_accumulator(value);
     }
 
-    fn and(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.bus.memory_read_u8(addr);
-        self.reg_a = self.reg_a & value;
+    pub(crate) fn and(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.reg_a &= value;
         self.update_cpuflags(self.reg_a);
     }
 
-    fn asl_accumulator(&mut self) {
-        let value = self.reg_a;
+    fn shift_left(&mut self, value: u8) -> u8 {
         self.status.set(CpuFlags::CARRY, value & 0x80 == 0x80);
-        self.reg_a = value << 1;
-        self.update_cpuflags(self.reg_a);
+        let result = value << 1;
+        self.update_cpuflags(result);
+        return result;
     }
 
-    fn asl(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let mut value = self.bus.memory_read_u8(addr);
-        self.status.set(CpuFlags::CARRY, value & 0x80 == 0x80);
-        value = value << 1;
-        self.bus.memory_write_u8(addr, value);
-        self.update_cpuflags(value);
+    pub(crate) fn asl_accumulator(&mut self, _mode: &AddressingMode) {
+        self.reg_a = self.shift_left(self.reg_a);
     }
 
-    fn bcc(&mut self) {
-        if !self.status.contains(CpuFlags::CARRY) {
-            self.branch();
-        }
+    pub(crate) fn asl(&mut self, mode: &AddressingMode) {
+        self.read_modify_write(mode, Self::shift_left);
     }
 
-    fn bcs(&mut self) {
-        if self.status.contains(CpuFlags::CARRY) {
-            self.branch();
-        }
+    pub(crate) fn bcc(&mut self, _mode: &AddressingMode) {
+        self.branch(!self.status.contains(CpuFlags::CARRY));
     }
 
-    fn beq(&mut self) {
-        if self.status.contains(CpuFlags::ZERO) {
-            self.branch();
-        }
+    pub(crate) fn bcs(&mut self, _mode: &AddressingMode) {
+        self.branch(self.status.contains(CpuFlags::CARRY));
+    }
+
+    pub(crate) fn beq(&mut self, _mode: &AddressingMode) {
+        self.branch(self.status.contains(CpuFlags::ZERO));
     }
 
-    fn bit(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.bus.memory_read_u8(addr);
+    pub(crate) fn bit(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.status.set(CpuFlags::ZERO, self.reg_a & value == 0x00);
         self.status.set(CpuFlags::OVERFLOW, value & 0x40 == 0x40);
         self.status.set(CpuFlags::NEGATIVE, value & 0x80 == 0x80);
     }
 
-    fn bmi(&mut self) {
-        if self.status.contains(CpuFlags::NEGATIVE) {
-            self.branch();
-        }
+    pub(crate) fn bmi(&mut self, _mode: &AddressingMode) {
+        self.branch(self.status.contains(CpuFlags::NEGATIVE));
     }
 
-    fn bne(&mut self) {
-        if !self.status.contains(CpuFlags::ZERO) {
-            self.branch();
-        }
+    pub(crate) fn bne(&mut self, _mode: &AddressingMode) {
+        self.branch(!self.status.contains(CpuFlags::ZERO));
     }
 
-    fn bpl(&mut self) {
-        if !self.status.contains(CpuFlags::NEGATIVE) {
-            self.branch();
-        }
+    pub(crate) fn bpl(&mut self, _mode: &AddressingMode) {
+        self.branch(!self.status.contains(CpuFlags::NEGATIVE));
     }
 
-    fn bvc(&mut self) {
-        if !self.status.contains(CpuFlags::OVERFLOW) {
-            self.branch();
+    pub(crate) fn brk(&mut self, _mode: &AddressingMode) {
+        if self.stop_on_brk {
+            self.stop();
+        } else {
+            self.interrupt(interrupt::BRK);
         }
     }
 
-    fn bvs(&mut self) {
-        if self.status.contains(CpuFlags::OVERFLOW) {
-            self.branch();
-        }
+    pub(crate) fn bvc(&mut self, _mode: &AddressingMode) {
+        self.branch(!self.status.contains(CpuFlags::OVERFLOW));
+    }
+
+    pub(crate) fn bvs(&mut self, _mode: &AddressingMode) {
+        self.branch(self.status.contains(CpuFlags::OVERFLOW));
     }
 
-    fn clc(&mut self) {
+    pub(crate) fn clc(&mut self, _mode: &AddressingMode) {
         self.status.set(CpuFlags::CARRY, false);
     }
 
-    fn cld(&mut self) {
+    pub(crate) fn cld(&mut self, _mode: &AddressingMode) {
         self.status.set(CpuFlags::DECIMAL_MODE, false);
     }
 
-    fn cli(&mut self) {
+    pub(crate) fn cli(&mut self, _mode: &AddressingMode) {
         self.status.set(CpuFlags::INTERRUPT_DISABLE, false);
     }
 
-    fn clv(&mut self) {
+    pub(crate) fn clv(&mut self, _mode: &AddressingMode) {
         self.status.set(CpuFlags::OVERFLOW, false);
     }
 
-    fn cmp(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value =  self.bus.memory_read_u8(addr);
+    pub(crate) fn cmp(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.compare(self.reg_a, value);
     }
 
-    fn cpx(&mut self, mode: &AddressingMode) {
-        let addr =  self.get_operand_address(mode);
-        let value =  self.bus.memory_read_u8(addr);
+    pub(crate) fn cpx(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.compare(self.reg_x, value);
     }
 
-    fn cpy(&mut self, mode: &AddressingMode) {
-        let addr =  self.get_operand_address(mode);
-        let value =  self.bus.memory_read_u8(addr);
+    pub(crate) fn cpy(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.compare(self.reg_y, value);
     }
 
-    fn dec(&mut self, mode: &AddressingMode) {
-        let addr =  self.get_operand_address(mode);
-        let mut value =  self.bus.memory_read_u8(addr);
-        value = value.wrapping_sub(1);
-        self.bus.memory_write_u8(addr, value);
-        self.update_cpuflags(value);
+    fn decrement(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.update_cpuflags(result);
+        return result;
     }
 
-    fn dex(&mut self) {
+    pub(crate) fn dec(&mut self, mode: &AddressingMode) {
+        self.read_modify_write(mode, Self::decrement);
+    }
+
+    pub(crate) fn dex(&mut self, _mode: &AddressingMode) {
         self.reg_x = self.reg_x.wrapping_sub(1);
         self.update_cpuflags(self.reg_x);
     }
 
-    fn dey(&mut self) {
+    pub(crate) fn dey(&mut self, _mode: &AddressingMode) {
         self.reg_y = self.reg_y.wrapping_sub(1);
         self.update_cpuflags(self.reg_y);
     }
 
-    fn eor(&mut self, mode: &AddressingMode) {
-        let addr =  self.get_operand_address(mode);
-        let value =  self.bus.memory_read_u8(addr);
-        self.reg_a = self.reg_a ^ value;
+    pub(crate) fn eor(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.reg_a ^= value;
         self.update_cpuflags(self.reg_a);
     }
 
-    fn inc(&mut self, mode: &AddressingMode) {
-        let addr =  self.get_operand_address(mode);
-        let mut value =  self.bus.memory_read_u8(addr);
-        value = value.wrapping_add(1);
-        self.bus.memory_write_u8(addr, value);
-        self.update_cpuflags(value);
+    fn increment(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.update_cpuflags(result);
+        return result;
+    }
+
+    pub(crate) fn inc(&mut self, mode: &AddressingMode) {
+        self.read_modify_write(mode, Self::increment);
     }
 
-    fn inx(&mut self) {
+    pub(crate) fn inx(&mut self, _mode: &AddressingMode) {
         self.reg_x = self.reg_x.wrapping_add(1);
         self.update_cpuflags(self.reg_x);
     }
 
-    fn iny(&mut self) {
+    pub(crate) fn iny(&mut self, _mode: &AddressingMode) {
         self.reg_y = self.reg_y.wrapping_add(1);
         self.update_cpuflags(self.reg_y);
     }
 
-    fn jmp(&mut self) {
-        let addr =  self.bus.memory_read_u16(self.reg_pc);
-        self.reg_pc = addr;
+    pub(crate) fn jmp(&mut self, _mode: &AddressingMode) {
+        self.reg_pc = self.fetch_u16();
     }
 
-    fn jmp_indirect(&mut self) {
-        let addr =  self.bus.memory_read_u16(self.reg_pc);
-        let indirect = if addr & 0x00ff == 0x00ff {
-            // reproduction 6502 bug
-            let lo = self.bus.memory_read_u8(addr);
-            let hi = self.bus.memory_read_u8(addr & 0xff00);
-            (hi as u16) << 8  | (lo as u16)
-        } else {
-            self.bus.memory_read_u16(addr)
-        };
-        self.reg_pc = indirect;
+    pub(crate) fn jmp_indirect(&mut self, _mode: &AddressingMode) {
+        let addr = self.fetch_u16();
+        let lo = self.read(addr);
+        // reproduction 6502 bug: the pointer does not carry into the high byte
+        let hi = self.read((addr & 0xff00) | (addr.wrapping_add(1) & 0x00ff));
+        self.reg_pc = (hi as u16) << 8 | (lo as u16);
     }
 
-    fn jsr(&mut self) {
-        self.stack_push_u16(self.reg_pc + 2 - 1);
-        let addr = self.get_operand_address(&AddressingMode::Immediate);
-        self.reg_pc = self.bus.memory_read_u16(addr);
+    pub(crate) fn jsr(&mut self, _mode: &AddressingMode) {
+        let lo = self.fetch() as u16;
+        self.read(STACK_BASE + self.reg_sp as u16);
+        // the pushed address points at the last byte of the instruction
+        self.stack_push_u16(self.reg_pc);
+        let hi = self.read(self.reg_pc) as u16;
+        self.reg_pc = hi << 8 | lo;
     }
 
-    fn lda(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.bus.memory_read_u8(addr);
+    pub(crate) fn lda(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.reg_a = value;
         self.update_cpuflags(self.reg_a);
     }
 
-    fn ldx(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.bus.memory_read_u8(addr);
+    pub(crate) fn ldx(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.reg_x = value;
         self.update_cpuflags(self.reg_x);
     }
 
-    fn ldy(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value = self.bus.memory_read_u8(addr);
+    pub(crate) fn ldy(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
         self.reg_y = value;
         self.update_cpuflags(self.reg_y);
     }
 
-    fn lsr_accumulator(&mut self) {
-        let value = self.reg_a;
+    fn shift_right(&mut self, value: u8) -> u8 {
         self.status.set(CpuFlags::CARRY, value & 0x01 == 0x01);
-        self.reg_a = value >> 1;
-        self.update_cpuflags(self.reg_a);
+        let result = value >> 1;
+        self.update_cpuflags(result);
+        return result;
     }
 
-    fn lsr(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let mut value = self.bus.memory_read_u8(addr);
-        self.status.set(CpuFlags::CARRY, value & 0x01 == 0x01);
-        value = value >> 1;
-        self.bus.memory_write_u8(addr, value);
-        self.update_cpuflags(value);
+    pub(crate) fn lsr_accumulator(&mut self, _mode: &AddressingMode) {
+        self.reg_a = self.shift_right(self.reg_a);
+    }
+
+    pub(crate) fn lsr(&mut self, mode: &AddressingMode) {
+        self.read_modify_write(mode, Self::shift_right);
     }
 
-    fn nop(&mut self) {
+    pub(crate) fn nop(&mut self, _mode: &AddressingMode) {
         return;
     }
 
-    fn ora(&mut self, mode: &AddressingMode) {
-        let addr =  self.get_operand_address(mode);
-        let value =  self.bus.memory_read_u8(addr);
-        self.reg_a = self.reg_a | value;
+    pub(crate) fn ora(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.reg_a |= value;
         self.update_cpuflags(self.reg_a);
     }
 
-    fn pha(&mut self) {
+    pub(crate) fn pha(&mut self, _mode: &AddressingMode) {
         self.stack_push_u8(self.reg_a);
     }
 
-    fn php(&mut self) {
-        let mut flags = self.status.clone();
+    pub(crate) fn php(&mut self, _mode: &AddressingMode) {
+        let mut flags = self.status;
         flags.set(CpuFlags::BREAK1, true);
         flags.set(CpuFlags::BREAK2, true);
         self.stack_push_u8(flags.bits());
     }
 
-    fn pla(&mut self) {
+    pub(crate) fn pla(&mut self, _mode: &AddressingMode) {
+        self.read(STACK_BASE + self.reg_sp as u16);
         self.reg_a = self.stack_pop_u8();
         self.update_cpuflags(self.reg_a);
     }
 
-    fn plp(&mut self) {
+    pub(crate) fn plp(&mut self, _mode: &AddressingMode) {
+        self.read(STACK_BASE + self.reg_sp as u16);
         self.status.bits = self.stack_pop_u8();
         self.status.set(CpuFlags::BREAK1, false);
         self.status.set(CpuFlags::BREAK2, true);
     }
 
-    fn rol_accumulator(&mut self) {
-        let value = self.reg_a;
+    fn rotate_left(&mut self, value: u8) -> u8 {
         let old_carry = self.status.contains(CpuFlags::CARRY);
         self.status.set(CpuFlags::CARRY, value & 0x80 == 0x80);
-        self.reg_a = value << 1;
+        let mut result = value << 1;
         if old_carry {
-            self.reg_a |= 0x01;
+            result |= 0x01;
         }
-        self.update_cpuflags(self.reg_a);
+        self.update_cpuflags(result);
+        return result;
     }
 
-    fn rol(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let mut value = self.bus.memory_read_u8(addr);
-        let old_carry = self.status.contains(CpuFlags::CARRY);
-        self.status.set(CpuFlags::CARRY, value & 0x80== 0x80);
-        value = value << 1;
-        if old_carry {
-            value |= 0x01;
-        }
-        self.bus.memory_write_u8(addr, value);
-        self.update_cpuflags(value);
+    pub(crate) fn rol_accumulator(&mut self, _mode: &AddressingMode) {
+        self.reg_a = self.rotate_left(self.reg_a);
     }
 
-    fn ror_accumulator(&mut self) {
-        let value = self.reg_a;
-        let old_carry = self.status.contains(CpuFlags::CARRY);
-        self.status.set(CpuFlags::CARRY, value & 0x01 == 0x01);
-        self.reg_a = value >> 1;
-        if old_carry {
-            self.reg_a |= 0x80;
-        }
-        self.update_cpuflags(self.reg_a);
+    pub(crate) fn rol(&mut self, mode: &AddressingMode) {
+        self.read_modify_write(mode, Self::rotate_left);
     }
 
-    fn ror(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let mut value = self.bus.memory_read_u8(addr);
+    fn rotate_right(&mut self, value: u8) -> u8 {
         let old_carry = self.status.contains(CpuFlags::CARRY);
         self.status.set(CpuFlags::CARRY, value & 0x01 == 0x01);
-        value = value >> 1;
+        let mut result = value >> 1;
         if old_carry {
-            value |= 0x80;
+            result |= 0x80;
         }
-        self.bus.memory_write_u8(addr, value);
-        self.update_cpuflags(value);
+        self.update_cpuflags(result);
+        return result;
+    }
+
+    pub(crate) fn ror_accumulator(&mut self, _mode: &AddressingMode) {
+        self.reg_a = self.rotate_right(self.reg_a);
+    }
+
+    pub(crate) fn ror(&mut self, mode: &AddressingMode) {
+        self.read_modify_write(mode, Self::rotate_right);
     }
 
-    fn rti(&mut self) {
+    pub(crate) fn rti(&mut self, _mode: &AddressingMode) {
+        self.read(STACK_BASE + self.reg_sp as u16);
         self.status.bits = self.stack_pop_u8();
         self.status.set(CpuFlags::BREAK1, false);
         self.status.set(CpuFlags::BREAK2, true);
         self.reg_pc = self.stack_pop_u16();
     }
 
-    fn rts(&mut self) {
-        self.reg_pc = self.stack_pop_u16() + 1;
+    pub(crate) fn rts(&mut self, _mode: &AddressingMode) {
+        self.read(STACK_BASE + self.reg_sp as u16);
+        self.reg_pc = self.stack_pop_u16();
+        self.fetch();
     }
 
-    fn sbc(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let value0 = self.bus.memory_read_u8(addr);
-        // Complement representation and subtract 1
-        let value = (value0 as i8).wrapping_neg().wrapping_sub(1);
-        self.add_accumulator(value as u8);
+    pub(crate) fn sbc(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.sub_accumulator(value);
     }
 
-    fn sec(&mut self) {
+    pub(crate) fn sec(&mut self, _mode: &AddressingMode) {
         self.status.set(CpuFlags::CARRY, true);
     }
 
-    fn sed(&mut self) {
+    pub(crate) fn sed(&mut self, _mode: &AddressingMode) {
         self.status.set(CpuFlags::DECIMAL_MODE, true);
     }
 
-    fn sei(&mut self) {
+    pub(crate) fn sei(&mut self, _mode: &AddressingMode) {
         self.status.set(CpuFlags::INTERRUPT_DISABLE, true);
     }
 
-    fn sta(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.bus.memory_write_u8(addr, self.reg_a);
+    pub(crate) fn sta(&mut self, mode: &AddressingMode) {
+        self.write_operand(mode, self.reg_a);
     }
 
-    fn stx(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.bus.memory_write_u8(addr, self.reg_x);
+    pub(crate) fn stx(&mut self, mode: &AddressingMode) {
+        self.write_operand(mode, self.reg_x);
     }
 
-    fn sty(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        self.bus.memory_write_u8(addr, self.reg_y);
+    pub(crate) fn sty(&mut self, mode: &AddressingMode) {
+        self.write_operand(mode, self.reg_y);
     }
 
-    fn tax(&mut self) {
+    pub(crate) fn tax(&mut self, _mode: &AddressingMode) {
         self.reg_x = self.reg_a;
         self.update_cpuflags(self.reg_x);
     }
 
-    fn tay(&mut self) {
+    pub(crate) fn tay(&mut self, _mode: &AddressingMode) {
         self.reg_y = self.reg_a;
         self.update_cpuflags(self.reg_y);
     }
 
-    fn tsx(&mut self) {
+    pub(crate) fn tsx(&mut self, _mode: &AddressingMode) {
         self.reg_x = self.reg_sp;
         self.update_cpuflags(self.reg_x);
     }
 
-    fn txa(&mut self) {
+    pub(crate) fn txa(&mut self, _mode: &AddressingMode) {
         self.reg_a = self.reg_x;
         self.update_cpuflags(self.reg_a);
     }
 
-    fn txs(&mut self) {
+    pub(crate) fn txs(&mut self, _mode: &AddressingMode) {
         self.reg_sp = self.reg_x;
     }
 
-    fn tya(&mut self) {
+    pub(crate) fn tya(&mut self, _mode: &AddressingMode) {
         self.reg_a = self.reg_y;
         self.update_cpuflags(self.reg_a);
     }
 
-    fn alr_unofficial(&mut self, mode: &AddressingMode) {
+    pub(crate) fn alr_unofficial(&mut self, mode: &AddressingMode) {
         self.and(mode);
-        self.lsr_accumulator();
+        self.reg_a = self.shift_right(self.reg_a);
     }
 
-    fn anc_unofficial(&mut self, mode: &AddressingMode) {
+    pub(crate) fn anc_unofficial(&mut self, mode: &AddressingMode) {
         self.and(mode);
         self.status.set(CpuFlags::CARRY, self.status.contains(CpuFlags::NEGATIVE));
     }
 
-    fn arr_unofficial(&mut self, mode: &AddressingMode) {
+    pub(crate) fn arr_unofficial(&mut self, mode: &AddressingMode) {
         self.and(mode);
-        self.ror_accumulator();
+        self.reg_a = self.rotate_right(self.reg_a);
         let bit5 = (self.reg_a & 0b0010_0000) != 0x00;
         let bit6 = (self.reg_a & 0b0100_0000) != 0x00;
         self.status.set(CpuFlags::CARRY, bit6);
         self.status.set(CpuFlags::OVERFLOW, bit5 ^ bit6);
     }
 
-    fn axs_unofficial(&mut self, mode: &AddressingMode) {
+    pub(crate) fn axs_unofficial(&mut self, mode: &AddressingMode) {
         let x_and_a = self.reg_x & self.reg_a;
-        let addr = self.get_operand_address(mode);
-        let data = self.bus.memory_read_u8(addr);
+        let data = self.read_operand(mode);
         let result = x_and_a.wrapping_sub(data);
         self.reg_x = result;
         self.status.set(CpuFlags::CARRY, x_and_a <= data);
         self.update_cpuflags(self.reg_x);
     }
 
-    fn lax_unofficial(&mut self, mode: &AddressingMode) {
+    pub(crate) fn lax_unofficial(&mut self, mode: &AddressingMode) {
         self.lda(mode);
-        self.tax();
+        self.reg_x = self.reg_a;
     }
 
-    fn sax_unofficial(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
+    pub(crate) fn sax_unofficial(&mut self, mode: &AddressingMode) {
         let data = self.reg_a & self.reg_x;
-        self.bus.memory_write_u8(addr, data);
+        self.write_operand(mode, data);
         // self.update_cpuflags(data);
     }
 
-    fn dcp_unofficial(&mut self, mode: &AddressingMode) {
-        let addr = self.get_operand_address(mode);
-        let mut data = self.bus.memory_read_u8(addr);
-        data = data.wrapping_sub(1);
-        self.bus.memory_write_u8(addr, data);
-        self.status.set(CpuFlags::CARRY, data <= self.reg_a);
-        self.update_cpuflags(self.reg_a.wrapping_sub(data));
+    pub(crate) fn dcp_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_modify_write(mode, Self::decrement);
+        self.compare(self.reg_a, data);
     }
 
-    fn isb_unofficial(&mut self, mode: &AddressingMode) {
-        self.inc(mode);
-        self.sbc(mode);
+    pub(crate) fn isb_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_modify_write(mode, Self::increment);
+        self.sub_accumulator(data);
     }
 
-    fn rla_unofficial(&mut self, mode: &AddressingMode) {
-        self.rol(mode);
-        self.and(mode);
+    pub(crate) fn rla_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_modify_write(mode, Self::rotate_left);
+        self.reg_a &= data;
+        self.update_cpuflags(self.reg_a);
     }
 
-    fn rra_unofficial(&mut self, mode: &AddressingMode) {
-        self.ror(mode);
-        self.adc(mode);
+    pub(crate) fn rra_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_modify_write(mode, Self::rotate_right);
+        self.add_accumulator(data);
     }
 
-    fn slo_unofficial(&mut self, mode: &AddressingMode) {
-        self.asl(mode);
-        self.ora(mode);
+    pub(crate) fn slo_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_modify_write(mode, Self::shift_left);
+        self.reg_a |= data;
+        self.update_cpuflags(self.reg_a);
     }
 
-    fn sre_unofficial(&mut self, mode: &AddressingMode) {
-        self.lsr(mode);
-        self.eor(mode);
+    pub(crate) fn sre_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_modify_write(mode, Self::shift_right);
+        self.reg_a ^= data;
+        self.update_cpuflags(self.reg_a);
     }
 
-    fn sbc_unofficial(&mut self, mode: &AddressingMode) {
+    pub(crate) fn sbc_unofficial(&mut self, mode: &AddressingMode) {
         self.sbc(mode);
     }
 
-    fn nop_unofficial(&mut self) {
-        return;
+    pub(crate) fn xaa_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_operand(mode);
+        self.reg_a = (self.reg_a | self.magic_constant) & self.reg_x & data;
+        self.update_cpuflags(self.reg_a);
+    }
+
+    pub(crate) fn lxa_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_operand(mode);
+        self.reg_a = (self.reg_a | self.magic_constant) & data;
+        self.reg_x = self.reg_a;
+        self.update_cpuflags(self.reg_a);
+    }
+
+    pub(crate) fn las_unofficial(&mut self, mode: &AddressingMode) {
+        let data = self.read_operand(mode) & self.reg_sp;
+        self.reg_a = data;
+        self.reg_x = data;
+        self.reg_sp = data;
+        self.update_cpuflags(data);
+    }
+
+    // SHA, SHX, SHY and TAS store the value ANDed with the high byte of the base address + 1
+    fn store_and_high_unofficial(&mut self, mode: &AddressingMode, index: u8, value: u8) {
+        let addr = self.fetch_operand_address(mode, Access::Write);
+        let base = addr.wrapping_sub(index as u16);
+        let data = value & ((base >> 8) as u8).wrapping_add(1);
+        // the high byte of the address is replaced by the value when the page is crossed
+        let addr = if page_crossed(base, addr) {
+            (data as u16) << 8 | (addr & 0x00FF)
+        } else {
+            addr
+        };
+        self.write(addr, data);
+    }
+
+    pub(crate) fn sha_unofficial(&mut self, mode: &AddressingMode) {
+        self.store_and_high_unofficial(mode, self.reg_y, self.reg_a & self.reg_x);
+    }
+
+    pub(crate) fn shx_unofficial(&mut self, mode: &AddressingMode) {
+        self.store_and_high_unofficial(mode, self.reg_y, self.reg_x);
+    }
+
+    pub(crate) fn shy_unofficial(&mut self, mode: &AddressingMode) {
+        self.store_and_high_unofficial(mode, self.reg_x, self.reg_y);
+    }
+
+    pub(crate) fn tas_unofficial(&mut self, mode: &AddressingMode) {
+        self.reg_sp = self.reg_a & self.reg_x;
+        self.store_and_high_unofficial(mode, self.reg_y, self.reg_sp);
+    }
+
+    pub(crate) fn jam_unofficial(&mut self, _mode: &AddressingMode) {
+        // PC stays on the JAM opcode
+        self.reg_pc = self.reg_pc.wrapping_sub(1);
+        self.jammed = true;
     }
 
-    fn nop_with_read_unofficial(&mut self) {
-        // let addr = self.get_operand_address(mode);
-        // let data = self.bus.memory_read_u8(addr);
+    pub(crate) fn nop_unofficial(&mut self, _mode: &AddressingMode) {
         return;
     }
 
+    pub(crate) fn nop_with_read_unofficial(&mut self, mode: &AddressingMode) {
+        self.read_operand(mode);
+    }
+
     fn update_cpuflags(&mut self, data: u8) {
         self.status.set(CpuFlags::ZERO, data == 0);
         self.status.set(CpuFlags::NEGATIVE, data & 0b1000_0000 != 0);
     }
 
     fn add_accumulator(&mut self, value: u8) {
-        let sum = self.reg_a as u16 + value as u16 + if self.status.contains(CpuFlags::CARRY) { 1 as u16 } else { 0 as u16};
+        let sum = self.reg_a as u16 + value as u16 + if self.status.contains(CpuFlags::CARRY) { 1 } else { 0 };
         self.status.set(CpuFlags::CARRY, sum > 0xff);
         let result = (sum & 0x00ff) as u8;
         self.status.set(CpuFlags::OVERFLOW, (result ^ value) & (result ^ self.reg_a) & 0x80 != 0x00 );
@@ -629,9 +864,24 @@ impl<'a> CPU<'a> {
         self.update_cpuflags(self.reg_a);
     }
 
-    fn branch(&mut self) {
-        let dst = self.bus.memory_read_u8(self.reg_pc) as i8;
-        let addr = self.reg_pc.wrapping_add(1).wrapping_add(dst as u16);
+    fn sub_accumulator(&mut self, value: u8) {
+        // Complement representation and subtract 1
+        let value = (value as i8).wrapping_neg().wrapping_sub(1);
+        self.add_accumulator(value as u8);
+    }
+
+    fn branch(&mut self, condition: bool) {
+        let dst = self.fetch() as i8;
+        if !condition {
+            return;
+        }
+        let next = self.reg_pc;
+        let addr = next.wrapping_add(dst as u16);
+        // +1 cycle if taken, +1 more to fix up the high byte of a new page
+        self.read(next);
+        if page_crossed(next, addr) {
+            self.read((next & 0xFF00) | (addr & 0x00FF));
+        }
         self.reg_pc = addr;
     }
 
@@ -641,7 +891,7 @@ impl<'a> CPU<'a> {
     }
 
     fn stack_push_u8(&mut self, data: u8) {
-        self.bus.memory_write_u8((STACK_BASE as u16) + (self.reg_sp as u16), data);
+        self.write(STACK_BASE + (self.reg_sp as u16), data);
         self.reg_sp = self.reg_sp.wrapping_sub(1);
     }
 
@@ -654,7 +904,7 @@ impl<'a> CPU<'a> {
 
     fn stack_pop_u8(&mut self) -> u8 {
         self.reg_sp = self.reg_sp.wrapping_add(1);
-        return self.bus.memory_read_u8((STACK_BASE as u16) + (self.reg_sp as u16));
+        return self.read(STACK_BASE + (self.reg_sp as u16));
     }
 
     fn stack_pop_u16(&mut self) -> u16 {
@@ -663,24 +913,50 @@ impl<'a> CPU<'a> {
         return hi << 8 | lo;
     }
 
+    fn read_vector(&mut self, vector_address: u16) -> u16 {
+        let lo = self.read(vector_address) as u16;
+        let hi = self.read(vector_address + 1) as u16;
+        return hi << 8 | lo;
+    }
+
     pub fn reset(&mut self) {
         self.reg_a = 0;
         self.reg_x = 0;
         self.reg_y = 0;
-        self.reg_sp = STACK_RESET;
         self.status = CpuFlags::INTERRUPT_DISABLE | CpuFlags::BREAK2;
-        self.reg_pc = self.bus.memory_read_u16(0xFFFC);
+        self.stopped = false;
+        self.jammed = false;
+        // the reset sequence is an interrupt with the stack writes turned into reads
+        self.read(self.reg_pc);
+        self.read(self.reg_pc);
+        self.reg_sp = 0;
+        for _ in 0 .. 3 {
+            self.read(STACK_BASE + self.reg_sp as u16);
+            self.reg_sp = self.reg_sp.wrapping_sub(1);
+        }
+        self.reg_pc = self.read_vector(0xFFFC);
     }
 
-    fn interrupt(&mut self, interrupt: interrupt::Interrupt) {
+    fn interrupt(&mut self, mut interrupt: interrupt::Interrupt) {
+        if interrupt.interrupt_type == interrupt::InterruptType::BRK {
+            // the byte after BRK is padding, so the return address skips it
+            self.fetch();
+        } else {
+            // the opcode of the interrupted instruction is fetched and discarded
+            self.read(self.reg_pc);
+            self.read(self.reg_pc);
+        }
         self.stack_push_u16(self.reg_pc);
-        let mut flags = self.status.clone();
+        // an NMI raised before the flags are pushed hijacks the vector of BRK and IRQ
+        if interrupt.interrupt_type != interrupt::InterruptType::NMI && self.bus.poll_nmi() {
+            interrupt.vector_address = interrupt::NMI.vector_address;
+        }
+        let mut flags = self.status;
         flags.set(CpuFlags::BREAK1, interrupt.break_flag_mask & 0x10 == 0x10);
         flags.set(CpuFlags::BREAK2, interrupt.break_flag_mask & 0x20 == 0x20);
         self.stack_push_u8(flags.bits);
         self.status.set(CpuFlags::INTERRUPT_DISABLE, true);
-        self.bus.tick(interrupt.cpu_cycles);
-        self.reg_pc = self.memory_read_u16(interrupt.vector_address);
+        self.reg_pc = self.read_vector(interrupt.vector_address);
     }
 
     #[allow(dead_code)]
@@ -697,6 +973,7 @@ impl<'a> CPU<'a> {
         self.load(program);
         self.reset();
         self.reg_pc = 0x0600;
+        self.stop_on_brk = true;
         self.run();
     }
 
@@ -706,355 +983,77 @@ impl<'a> CPU<'a> {
 
     pub fn run_with_callback<F>(&mut self, mut callback: F)
     where
-        F: FnMut(&mut CPU),
+        F: FnMut(&mut CPU<M>),
     {
-        let ref opcodes: HashMap<u8, &'static opcodes::OpCode> = *opcodes::OPCODE_MAP;
-
         loop {
-            if let Some(_nmi) = self.bus.poll_nmi() {
-                self.interrupt(interrupt::NMI);
+            self.handle_interrupts();
+            callback(self);
+            if self.stopped {
+                return;
+            }
+            self.execute();
+            if self.stopped || self.jammed {
+                return;
             }
+        }
+    }
 
-            callback(self);
+    // returns false once the CPU has been stopped or jammed
+    pub fn step(&mut self) -> bool {
+        if self.stopped || self.jammed {
+            return false;
+        }
+        self.handle_interrupts();
+        self.execute();
+        return !self.stopped && !self.jammed;
+    }
 
-            let code = self.bus.memory_read_u8(self.reg_pc);
-            self.reg_pc += 1;
-            let pc_state = self.reg_pc;
-            let opcode = opcodes.get(&code).expect(&format!("OpCode: {:?} is not recognized", code));
-
-            match opcode.code {
-                0x69 | 0x65 | 0x75 | 0x6d | 0x7d | 0x79 | 0x61 | 0x71 => {
-                    // ADC
-                    self.adc(&opcode.mode);
-                },
-                0x29 | 0x25 | 0x35 | 0x2d | 0x3d | 0x39 | 0x21 | 0x31 => {
-                    // AND
-                    self.and(&opcode.mode);
-                },
-                0x0a => {
-                    // ASL Accumulator
-                    self.asl_accumulator();
-                },
-                0x06 | 0x16 | 0x0e | 0x1e => {
-                    // ASL
-                    self.asl(&opcode.mode);
-                },
-                0x90 => {
-                    // BCC
-                    self.bcc();
-                },
-                0xb0 => {
-                    // BCS
-                    self.bcs();
-                },
-                0xf0 => {
-                    // BEQ
-                    self.beq();
-                },
-                0x24 | 0x2c => {
-                    // BIT
-                    self.bit(&opcode.mode);
-                },
-                0x30 => {
-                    // BMI
-                    self.bmi();
-                },
-                0xd0 => {
-                    // BNE
-                    self.bne();
-                },
-                0x10 => {
-                    // BPL
-                    self.bpl();
-                },
-                0x00 => {
-                    // BRK
-                    return;
-                },
-                0x50 => {
-                    // BVC
-                    self.bvc();
-                },
-                0x70 => {
-                    // BVS
-                    self.bvs();
-                },
-                0x18 => {
-                    // CLC
-                    self.clc();
-                },
-                0xd8 => {
-                    // CLD
-                    self.cld();
-                },
-                0x58 => {
-                    // CLI
-                    self.cli();
-                },
-                0xb8 => {
-                    // CLV
-                    self.clv();
-                },
-                0xc9 | 0xc5 | 0xd5 | 0xcd | 0xdd | 0xd9 | 0xc1 | 0xd1 => {
-                    // CMP
-                    self.cmp(&opcode.mode);
-                },
-                0xe0 | 0xe4 | 0xec => {
-                    // CPX
-                    self.cpx(&opcode.mode);
-                },
-                0xc0 | 0xc4 | 0xcc => {
-                    // CPY
-                    self.cpy(&opcode.mode);
-                },
-                0xc6 | 0xd6 | 0xce | 0xde => {
-                    // DEC
-                    self.dec(&opcode.mode);
-                },
-                0xca => {
-                    // DEX
-                    self.dex();
-                },
-                0x88 => {
-                    // DEY
-                    self.dey();
-                },
-                0x49 | 0x45 | 0x55 | 0x4d | 0x5d | 0x59 | 0x41 | 0x51 => {
-                    // EOR
-                    self.eor(&opcode.mode);
-                },
-                0xe6 | 0xf6 | 0xee | 0xfe => {
-                    // INC
-                    self.inc(&opcode.mode);
-                },
-                0xe8 => {
-                    // INX
-                    self.inx();
-                },
-                0xc8 => {
-                    // INY
-                    self.iny();
-                },
-                0x4c => {
-                    // JMP
-                    self.jmp();
-                },
-                0x6c => {
-                    // JMP indirect
-                    self.jmp_indirect();
-                },
-                0x20 => {
-                    // JSR
-                    self.jsr();
-                },
-                0xa9 | 0xa5 | 0xb5 | 0xad | 0xbd | 0xb9 | 0xa1 | 0xb1 => {
-                    // LDA
-                    self.lda(&opcode.mode);
-                },
-                0xa2 | 0xa6 | 0xb6 | 0xae | 0xbe => {
-                    // LDX
-                    self.ldx(&opcode.mode);
-                },
-                0xa0 | 0xa4 | 0xb4 | 0xac | 0xbc => {
-                    // LDY
-                    self.ldy(&opcode.mode);
-                },
-                0x4a => {
-                    // LSR Accumulator
-                    self.lsr_accumulator();
-                },
-                0x46 | 0x56 | 0x4e | 0x5e => {
-                    // LSR
-                    self.lsr(&opcode.mode);
-                },
-                0xea => {
-                    // NOP
-                    self.nop();
-                },
-                0x09 | 0x05 | 0x15 | 0x0d | 0x1d | 0x19 | 0x01 | 0x11 => {
-                    // ORA
-                    self.ora(&opcode.mode);
-                },
-                0x48 => {
-                    // PHA
-                    self.pha();
-                },
-                0x08 => {
-                    // PHP
-                    self.php();
-                },
-                0x68 => {
-                    // PLA
-                    self.pla();
-                },
-                0x28 => {
-                    // PHP
-                    self.plp();
-                },
-                0x2a => {
-                    // ROL Accumulator
-                    self.rol_accumulator();
-                },
-                0x26 | 0x36 | 0x2e | 0x3e => {
-                    // ROL
-                    self.rol(&opcode.mode);
-                },
-                0x6a => {
-                    // ROR Accumulator
-                    self.ror_accumulator();
-                },
-                0x66 | 0x76 | 0x6e | 0x7e => {
-                    // ROR
-                    self.ror(&opcode.mode);
-                },
-                0x40 => {
-                    // RTI
-                    self.rti();
-                },
-                0x60 => {
-                    // RTS
-                    self.rts();
-                },
-                0xe9 | 0xe5 | 0xf5 | 0xed | 0xfd | 0xf9 | 0xe1 | 0xf1 => {
-                    // SBC
-                    self.sbc(&opcode.mode);
-                },
-                0x38 => {
-                    // SEC
-                    self.sec();
-                },
-                0xf8 => {
-                    // SED
-                    self.sed();
-                },
-                0x78 => {
-                    // SEI
-                    self.sei();
-                },
-                0x85 | 0x95 | 0x8d | 0x9d | 0x99 | 0x81 | 0x91 => {
-                    // STA
-                    self.sta(&opcode.mode);
-                },
-                0x86 | 0x96 | 0x8e => {
-                    // STX
-                    self.stx(&opcode.mode);
-                },
-                0x84 | 0x94 | 0x8c => {
-                    //STY
-                    self.sty(&opcode.mode);
-                },
-                0xaa => {
-                    // TAX
-                    self.tax();
-                },
-                0xa8 => {
-                    // TAY
-                    self.tay();
-                },
-                0xba => {
-                    // TSX
-                    self.tsx();
-                },
-                0x8a => {
-                    // TXA
-                    self.txa();
-                },
-                0x9a => {
-                    // TXS
-                    self.txs();
-                },
-                0x98 => {
-                    // TYA
-                    self.tya();
-                },
-                // ========== unofficial opcodes ==========
-                0x4b => {
-                    // ALR
-                    self.alr_unofficial(&opcode.mode);
-                },
-                0x0b | 0x2b => {
-                    // ANC
-                    self.anc_unofficial(&opcode.mode);
-                },
-                0x6b => {
-                    // ARR
-                    self.arr_unofficial(&opcode.mode);
-                },
-                0xcb => {
-                    // AXS
-                    self.axs_unofficial(&opcode.mode);
-                },
-                0xa7 | 0xb7 | 0xaf | 0xbf | 0xa3 | 0xb3 => {
-                    // LAX
-                    self.lax_unofficial(&opcode.mode);
-                },
-                0x87 | 0x97 | 0x8f | 0x83 => {
-                    // SAX
-                    self.sax_unofficial(&opcode.mode);
-                },
-                0xc7 | 0xd7 | 0xcf | 0xdf | 0xdb | 0xd3 | 0xc3 => {
-                    // DCP
-                    self.dcp_unofficial(&opcode.mode);
-                },
-                0xe7 | 0xf7 | 0xef | 0xff | 0xfb | 0xe3 | 0xf3 => {
-                    // ISB
-                    self.isb_unofficial(&opcode.mode);
-                },
-                0x27 | 0x37 | 0x2f | 0x3f | 0x3b | 0x33 | 0x23 => {
-                    // RLA
-                    self.rla_unofficial(&opcode.mode);
-                },
-                0x67 | 0x77 | 0x6f | 0x7f | 0x7b | 0x63 | 0x73 => {
-                    // RRA
-                    self.rra_unofficial(&opcode.mode);
-                },
-                0x07 | 0x17 | 0x0f | 0x1f | 0x1b | 0x03 | 0x13 => {
-                    // SLO
-                    self.slo_unofficial(&opcode.mode);
-                },
-                0x47 | 0x57 | 0x4f | 0x5f | 0x5b | 0x43 | 0x53 => {
-                    // SRE
-                    self.sre_unofficial(&opcode.mode);
-                },
-                0xeb => {
-                    // SBC
-                    self.sbc_unofficial(&opcode.mode);
-                },
-                0x80 | 0x82 | 0x89 | 0xc2 | 0xe2 => {
-                    // NOP immediate
-                    self.nop_unofficial();
-                },
-                0x04 | 0x44 | 0x64 | 0x14 | 0x34 | 0x54 | 0x74 | 0xd4 | 0xf4
-                | 0x0c | 0x1c | 0x3c | 0x5c | 0x7c | 0xdc | 0xfc => {
-                    // NOP with read
-                    self.nop_with_read_unofficial();
-                },
-                | 0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72
-                | 0x92 | 0xb2 | 0xd2 | 0xf2 | 0x1a | 0x3a | 0x5a | 0x7a | 0xda | 0xfa => {
-                    // NOP others
-                    self.nop_unofficial();
-                },
-                _ => todo!()
-            }
+    // ends run_with_callback and step after the current instruction
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
 
-            self.bus.tick(opcode.cycles); //sync clock for other modules
+    pub fn is_stopped(&self) -> bool {
+        return self.stopped;
+    }
 
-            if pc_state == self.reg_pc {
-                self.reg_pc += (opcode.len - 1) as u16;
-            }
+    pub fn is_jammed(&self) -> bool {
+        return self.jammed;
+    }
+
+    fn handle_interrupts(&mut self) {
+        if self.bus.poll_nmi() {
+            self.interrupt(interrupt::NMI);
+        } else if self.bus.poll_irq() && !self.status.contains(CpuFlags::INTERRUPT_DISABLE) {
+            self.interrupt(interrupt::IRQ);
         }
     }
+
+    fn execute(&mut self) {
+        let code = self.fetch();
+        let opcode = &opcodes::OPCODE_TABLE[code as usize];
+        if opcode.len == 1 && opcode.code != 0x00 {
+            // single byte instructions read the next byte and throw it away
+            self.read(self.reg_pc);
+        }
+
+        (Self::HANDLERS[code as usize])(self, &opcode.mode);
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::ppu::NesPPU;
-
     use super::*;
+    use crate::bus::BusAccess;
+    use crate::bus::FlatRam;
+    use crate::mapper::Mapper;
+    use crate::rom::Mirroring;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
     #[test]
     fn test_0x69_adc_immidiate_for_not_c() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x55, 0x69, 0x10, 0x00]);
         // 0x55 + 0x10 = 0x65, no CARRY, no OVERFLOW
@@ -1066,7 +1065,7 @@ mod test {
 
     #[test]
     fn test_0x69_adc_immidiate_for_c() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x55, 0x69, 0xcc, 0x00]);
         // 0x55 + 0xcc = 33+256, with CARRY, no OVERFLOW
@@ -1078,7 +1077,7 @@ mod test {
 
     #[test]
     fn test_0x69_adc_immidiate_for_v() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x40, 0x69, 0x40, 0x00]);
         // 64 + 64 = 128(=-128), with OVERFLOW
@@ -1090,7 +1089,7 @@ mod test {
 
     #[test]
     fn test_0x69_adc_immidiate_with_carry_for_cz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xff, 0xc9, 0x00, 0xa9, 0xfe, 0x69, 0x01, 0x00]);
         // 0xfe + 0x01 + CARRY = 0x00 + 256 with CARRY, no OVERFLOW
@@ -1102,7 +1101,7 @@ mod test {
 
     #[test]
     fn test_0x69_adc_immidiate_with_carried_overflow() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xff, 0xc9, 0x00, 0xa9, 0x80, 0x69, 0xff, 0x00]);
         // 0x80 + 0xff + CARRY = 0x80 + 256 with CARRY, no OVERFLOW
@@ -1114,7 +1113,7 @@ mod test {
 
     #[test]
     fn test_0x29_and_with_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xd5, 0x29, 0xab, 0x00]);
         assert_eq!(cpu.reg_a, 0b1000_0001);
@@ -1124,7 +1123,7 @@ mod test {
 
     #[test]
     fn test_0x0a_asl_accumulator() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1110_0101, 0x0a, 0x00]);
         assert_eq!(cpu.reg_a, 0b1100_1010);
@@ -1135,7 +1134,7 @@ mod test {
 
     #[test]
     fn test_0x06_asl_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1010_0101, 0x85, 0x03, 0x06, 0x03, 0x00]);
         assert_eq!(cpu.memory_read_u8(0x03), 0b0100_1010);
@@ -1158,7 +1157,7 @@ mod test {
 
     #[test]
     fn test_0x24_bit_zeropage_for_v_not_nz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x45, 0x85, 0x03, 0xa9, 0x01, 0x24, 0x03, 0x00]);
         assert!(!cpu.status.contains(CpuFlags::ZERO));
@@ -1168,7 +1167,7 @@ mod test {
 
     #[test]
     fn test_0x24_bit_zeropage_for_nz_not_v() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x80, 0x85, 0x03, 0xa9, 0x7f, 0x24, 0x03, 0x00]);
         assert!(cpu.status.contains(CpuFlags::ZERO));
@@ -1198,7 +1197,7 @@ mod test {
 
     #[test]
     fn test_0x18_clc() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x80, 0x0a, 0x18, 0x00]);
         assert!(!cpu.status.contains(CpuFlags::CARRY))
@@ -1214,7 +1213,7 @@ mod test {
 
     #[test]
     fn test_0xb8_clv() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x40, 0x69, 0x40, 0xb8, 0x00]);
         assert_eq!(cpu.reg_a, 0x80);
@@ -1223,7 +1222,7 @@ mod test {
 
     #[test]
     fn test_0xc9_cmp_immidiate_for_cn_not_z() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x88, 0xc9, 0x04, 0x00]);
         assert!(cpu.status.contains(CpuFlags::CARRY));
@@ -1233,7 +1232,7 @@ mod test {
 
     #[test]
     fn test_0xc9_cmp_immidiate_for_cz_not_n() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x08, 0xc9, 0x08, 0x00]);
         assert!(cpu.status.contains(CpuFlags::CARRY));
@@ -1243,7 +1242,7 @@ mod test {
 
     #[test]
     fn test_0xc9_cmp_immidiate_for_n_not_cz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x00, 0xc9, 0x01, 0x00]);
         assert!(!cpu.status.contains(CpuFlags::CARRY));
@@ -1253,7 +1252,7 @@ mod test {
 
     #[test]
     fn test_0xe0_cpx_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa2, 0x88, 0xe0, 0x04, 0x00]);
         assert!(cpu.status.contains(CpuFlags::CARRY));
@@ -1263,7 +1262,7 @@ mod test {
 
     #[test]
     fn test_0xc0_cpy_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa0, 0x04, 0xc0, 0x88, 0x00]);
         assert!(!cpu.status.contains(CpuFlags::CARRY));
@@ -1273,7 +1272,7 @@ mod test {
 
     #[test]
     fn test_0xc6_dec_zeropage_for_not_nz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x04, 0x85, 0x05, 0xc6, 0x05, 0x00]);
         let data = cpu.memory_read_u8(0x05);
@@ -1284,7 +1283,7 @@ mod test {
 
     #[test]
     fn test_0xca_dex_for_z_not_n() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x01, 0xaa, 0xca, 0x00]);
         assert_eq!(cpu.reg_x, 0x00);
@@ -1294,7 +1293,7 @@ mod test {
 
     #[test]
     fn test_0x88_dey_for_n_not_z() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x00, 0xa8, 0x88, 0x00]);
         assert_eq!(cpu.reg_y, 0xff);
@@ -1304,7 +1303,7 @@ mod test {
 
     #[test]
     fn test_0x49_eor_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x50, 0x49, 0x14, 0x00]);
         assert_eq!(cpu.reg_a, 0x44);
@@ -1314,7 +1313,7 @@ mod test {
 
     #[test]
     fn test_0xe6_inc_zeropage_for_not_nz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x04, 0x85, 0x05, 0xe6, 0x05, 0x00]);
         let data = cpu.memory_read_u8(0x05);
@@ -1325,7 +1324,7 @@ mod test {
 
     #[test]
     fn test_0xe8_inx_for_z_not_n() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0x00]);
         assert_eq!(cpu.reg_x, 0x00);
@@ -1335,7 +1334,7 @@ mod test {
 
     #[test]
     fn test_0xe8_inx_overflow() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0xe8, 0x00]);
         assert_eq!(cpu.reg_x, 1);
@@ -1345,7 +1344,7 @@ mod test {
 
     #[test]
     fn test_0xc8_iny_for_n_not_z() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x7f, 0xa8, 0xc8, 0x00]);
         assert_eq!(cpu.reg_y, 0x80);
@@ -1355,7 +1354,7 @@ mod test {
 
     #[test]
     fn test_0x4c_jmp_absolute() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x0000, 0xa9);
         cpu.memory_write_u8(0x0001, 0xaa);
@@ -1370,7 +1369,7 @@ mod test {
 
     #[test]
     fn test_0x20_0x60_jsr_rts() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x0010, 0xa9);
         cpu.memory_write_u8(0x0011, 0x02);
@@ -1382,25 +1381,25 @@ mod test {
 
     #[test]
     fn test_0xa9_lda_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
         assert_eq!(cpu.reg_a, 0x05);
-        assert!(cpu.status.contains(CpuFlags::ZERO) == false);
-        assert!(cpu.status.contains(CpuFlags::NEGATIVE) == false);
+        assert!(!cpu.status.contains(CpuFlags::ZERO));
+        assert!(!cpu.status.contains(CpuFlags::NEGATIVE));
     }
 
     #[test]
     fn test_0xa9_lda_immidiate_for_z() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
-        assert!(cpu.status.contains(CpuFlags::ZERO) == true);
+        assert!(cpu.status.contains(CpuFlags::ZERO));
     }
 
     #[test]
     fn test_0xa5_lda_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0x55);
         cpu.load_and_run(vec![0xa5, 0x10, 0x00]);
@@ -1409,7 +1408,7 @@ mod test {
 
     #[test]
     fn test_0xa2_ldx_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa2, 0xa5, 0x00]);
         assert_eq!(cpu.reg_x, 0xa5);
@@ -1419,7 +1418,7 @@ mod test {
 
     #[test]
     fn test_0xa0_ldy_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa0, 0x5a, 0x00]);
         assert_eq!(cpu.reg_y, 0x5a);
@@ -1429,7 +1428,7 @@ mod test {
 
     #[test]
     fn test_0x4a_lsr_accumulator() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1110_0101, 0x4a, 0x00]);
         assert_eq!(cpu.reg_a, 0b0111_0010);
@@ -1440,7 +1439,7 @@ mod test {
 
     #[test]
     fn test_0x46_lsr_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1010_0101, 0x85, 0x03, 0x46, 0x03, 0x00]);
         assert_eq!(cpu.memory_read_u8(0x03), 0b0101_0010);
@@ -1451,7 +1450,7 @@ mod test {
 
     #[test]
     fn test_0xea_nop() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xea, 0xa9, 0x55, 0x00]);
         assert_eq!(cpu.reg_a, 0x55);
@@ -1459,7 +1458,7 @@ mod test {
 
     #[test]
     fn test_0x09_ora_immmidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b0101_1010, 0x09, 0b1001_0100, 0x00]);
         assert_eq!(cpu.reg_a, 0b1101_1110);
@@ -1469,7 +1468,7 @@ mod test {
 
     #[test]
     fn test_0x48_0x68_pha_pla() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x80, 0x48, 0xa9, 0x00, 0x68, 0x00]);
         assert_eq!(cpu.reg_a, 0x80);
@@ -1479,7 +1478,7 @@ mod test {
 
     #[test]
     fn test_0x08_0x28_php_plp() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x80, 0x08, 0xa9, 0x00, 0x28, 0x00]);
         assert_eq!(cpu.reg_a, 0x00);
@@ -1489,7 +1488,7 @@ mod test {
 
     #[test]
     fn test_0x2a_rol_accumulator_with_carry() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xff, 0xc9, 0x00, 0xa9, 0b1010_0000, 0x2a, 0x00]);
         assert_eq!(cpu.reg_a, 0b0100_0001);
@@ -1500,7 +1499,7 @@ mod test {
 
     #[test]
     fn test_0x26_rol_zeropage_without_carry() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b0010_0101, 0x85, 0x03, 0x26, 0x03, 0x00]);
         assert_eq!(cpu.memory_read_u8(0x03), 0b0100_1010);
@@ -1511,7 +1510,7 @@ mod test {
 
     #[test]
     fn test_0x6a_ror_accumulator_with_carry() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xff, 0xc9, 0x00, 0xa9, 0b1010_0000, 0x6a, 0x00]);
         assert_eq!(cpu.reg_a, 0b1101_0000);
@@ -1522,7 +1521,7 @@ mod test {
 
     #[test]
     fn test_0x66_ror_zeropage_without_carry() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1010_0001, 0x85, 0x03, 0x66, 0x03, 0x00]);
         assert_eq!(cpu.memory_read_u8(0x03), 0b0101_0000);
@@ -1533,7 +1532,7 @@ mod test {
 
     #[test]
     fn test_0xe9_sbc_immidiate_for_not_cz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x10, 0xe9, 0x01, 0x00]);
         // 16 - 1 - 1 = 14, no CARRY
@@ -1545,7 +1544,7 @@ mod test {
 
     #[test]
     fn test_0xe9_sbc_immidiate_for_v_not_cz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x80, 0xe9, 0x7f, 0x00]);
         // 0x80 - 0x7f - 1 = 0x00
@@ -1557,7 +1556,7 @@ mod test {
 
     #[test]
     fn test_0xe9_sbc_immidiate_with_carry_for_zv_not_c() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x80, 0x38, 0xe9, 0x7f, 0x00]);
         // 0x80 - 0x7f = 0x01 with OVERFLOW, no CARRY
@@ -1569,7 +1568,7 @@ mod test {
 
     #[test]
     fn test_0xe9_sbc_immidiate_with_carried_overflow() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x40, 0x38, 0xe9, 0xff, 0x00]);
         // 0x40 - 0xff = 0x41 with CARRY, no OVERFLOW
@@ -1581,7 +1580,7 @@ mod test {
 
     #[test]
     fn test_0x38_sec() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0x38, 0x00]);
         assert!(cpu.status.contains(CpuFlags::CARRY));
@@ -1589,7 +1588,7 @@ mod test {
 
     #[test]
     fn test_0xf8_sed() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xf8, 0x00]);
         assert!(cpu.status.contains(CpuFlags::DECIMAL_MODE));
@@ -1597,7 +1596,7 @@ mod test {
 
     #[test]
     fn test_0x78_sed() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0x78, 0x00]);
         assert!(cpu.status.contains(CpuFlags::INTERRUPT_DISABLE));
@@ -1605,7 +1604,7 @@ mod test {
 
     #[test]
     fn test_0x85_sta_to_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x55, 0x85, 0x03, 0x00]);
         let data = cpu.memory_read_u8(0x03);
@@ -1614,7 +1613,7 @@ mod test {
 
     #[test]
     fn test_0x95_sta_to_zeropage_x() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x55, 0xaa, 0xa9, 0xaa, 0x95, 0x03, 0x00]);
         let data = cpu.memory_read_u8(0x58);
@@ -1623,7 +1622,7 @@ mod test {
 
     #[test]
     fn test_0x86_stx_to_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa2, 0x55, 0x86, 0x03, 0x00]);
         let data = cpu.memory_read_u8(0x03);
@@ -1632,7 +1631,7 @@ mod test {
 
     #[test]
     fn test_0x84_sty_to_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa0, 0x55, 0x84, 0x03, 0x00]);
         let data = cpu.memory_read_u8(0x03);
@@ -1641,7 +1640,7 @@ mod test {
 
     #[test]
     fn test_0xaa_tax_move_a_to_x() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x0a, 0xaa, 0x00]);
         assert_eq!(cpu.reg_x, 10);
@@ -1649,7 +1648,7 @@ mod test {
 
     #[test]
     fn test_0xa8_tay_move_a_to_y() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x0a, 0xa8, 0x00]);
         assert_eq!(cpu.reg_y, 10);
@@ -1657,7 +1656,7 @@ mod test {
 
     #[test]
     fn test_0xba_tsx() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0x48, 0xba, 0x00]);
         assert_eq!(cpu.reg_sp, cpu.reg_x);
@@ -1665,7 +1664,7 @@ mod test {
 
     #[test]
     fn test_0x8a_txa() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa2, 0x55, 0x8a, 0x00]);
         assert_eq!(cpu.reg_x, cpu.reg_a);
@@ -1673,7 +1672,7 @@ mod test {
 
     #[test]
     fn test_0x9a_txs() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0x48, 0xba, 0xe8, 0x9a, 0x00]);
         assert_eq!(cpu.reg_x, cpu.reg_sp);
@@ -1681,7 +1680,7 @@ mod test {
 
     #[test]
     fn test_0x98_tya() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa0, 0x55, 0x98, 0x00]);
         assert_eq!(cpu.reg_y, cpu.reg_a);
@@ -1689,7 +1688,7 @@ mod test {
 
     #[test]
     fn test_0xe8_5_ops_working_together() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
         assert_eq!(cpu.reg_x, 0xc1);
@@ -1698,7 +1697,7 @@ mod test {
     // ========== unofficial opcodes ==========
     #[test]
     fn test_0xa7_lax_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0x55);
         cpu.load_and_run(vec![0xa7, 0x10, 0x00]);
@@ -1708,7 +1707,7 @@ mod test {
 
     #[test]
     fn test_0x87_sax_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x55, 0xa2, 0xa5, 0x87, 0x10, 0x00]);
         assert_eq!(cpu.bus.memory_read_u8(0x10), 0x05);
@@ -1718,7 +1717,7 @@ mod test {
 
     #[test]
     fn test_0xeb_sbc_immidiate_for_not_cz() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x10, 0xeb, 0x01, 0x00]);
         // 16 - 1 - 1 = 14, no CARRY
@@ -1730,7 +1729,7 @@ mod test {
 
     #[test]
     fn test_0xc7_dcp_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0x55);
         cpu.load_and_run(vec![0xa9, 0x54, 0xc7, 0x10, 0x00]);
@@ -1742,7 +1741,7 @@ mod test {
 
     #[test]
     fn test_0xe7_isb_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0x55);
         cpu.load_and_run(vec![0xa9, 0x57, 0xe7, 0x10, 0x00]);
@@ -1755,7 +1754,7 @@ mod test {
 
     #[test]
     fn test_0x07_slo_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0b1010_0001);
         cpu.load_and_run(vec![0x38, 0xa9, 0b0100_1000, 0x07, 0x10, 0x00]);
@@ -1768,7 +1767,7 @@ mod test {
 
     #[test]
     fn test_0x27_rla_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0b0010_0001);
         cpu.load_and_run(vec![0x38, 0xa9, 0b0100_1000, 0x27, 0x10, 0x00]);
@@ -1781,7 +1780,7 @@ mod test {
 
     #[test]
     fn test_0x47_sre_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0b0010_0001);
         cpu.load_and_run(vec![0xa9, 0b0100_1000, 0x47, 0x10, 0x00]);
@@ -1794,7 +1793,7 @@ mod test {
 
     #[test]
     fn test_0x67_rra_zeropage() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.memory_write_u8(0x10, 0b0010_0001);
         cpu.load_and_run(vec![0xa9, 0b0101_1000, 0x67, 0x10, 0x00]);
@@ -1807,7 +1806,7 @@ mod test {
 
     #[test]
     fn test_0x4b_alr_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b0101_1001, 0x4b, 0b0101_0001, 0x00]);
         assert_eq!(cpu.reg_a, 0b0010_1000);
@@ -1818,7 +1817,7 @@ mod test {
 
     #[test]
     fn test_0x0b_anc_immidiate() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1101_1001, 0x0b, 0b1101_0001, 0x00]);
         assert_eq!(cpu.reg_a, 0b1101_0001);
@@ -1829,7 +1828,7 @@ mod test {
 
     #[test]
     fn test_0x6b_arr_immidiate_for_c_not_v() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1101_1001, 0x6b, 0b1101_0001, 0x00]);
         assert_eq!(cpu.reg_a, 0b0110_1000);
@@ -1841,7 +1840,7 @@ mod test {
 
     #[test]
     fn test_0x6b_arr_immidiate_for_v_not_c() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b0101_1001, 0x6b, 0b0101_0001, 0x00]);
         assert_eq!(cpu.reg_a, 0b0010_1000);
@@ -1853,7 +1852,7 @@ mod test {
 
     #[test]
     fn test_0x6b_arr_immidiate_for_cv() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b1001_1001, 0x6b, 0b1001_0001, 0x00]);
         assert_eq!(cpu.reg_a, 0b0100_1000);
@@ -1865,7 +1864,7 @@ mod test {
 
     #[test]
     fn test_0x6b_arr_immidiate_for_not_cv() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0b0001_1001, 0x6b, 0b0001_0001, 0x00]);
         assert_eq!(cpu.reg_a, 0b0000_1000);
@@ -1877,7 +1876,7 @@ mod test {
 
     #[test]
     fn test_0xcb_axs_immidiate_for_not_c() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x3f, 0xa2, 0x1f, 0xcb, 0x04, 0x00]);
         assert_eq!(cpu.reg_x, 0b0001_1011);
@@ -1888,7 +1887,7 @@ mod test {
 
     #[test]
     fn test_0xcb_axs_immidiate_for_c() {
-        let bus = Bus::new(|_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let bus = Bus::new();
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x3f, 0xa2, 0x1f, 0xcb, 0x2f, 0x00]);
         assert_eq!(cpu.reg_x, 0b1111_0000);
@@ -1896,5 +1895,374 @@ mod test {
         assert!(cpu.status.contains(CpuFlags::NEGATIVE));
         assert!(cpu.status.contains(CpuFlags::CARRY));
     }
-}
 
+    #[test]
+    fn test_0xab_lxa_immidiate() {
+        let bus = Bus::new();
+        let mut cpu = CPU::new(bus);
+        cpu.magic_constant = 0xff;
+        cpu.load_and_run(vec![0xa9, 0x00, 0xab, 0x5a, 0x00]);
+        assert_eq!(cpu.reg_a, 0x5a);
+        assert_eq!(cpu.reg_x, 0x5a);
+    }
+
+    #[test]
+    fn test_0x8b_xaa_immidiate() {
+        let bus = Bus::new();
+        let mut cpu = CPU::new(bus);
+        // (0x01 | 0xee) & 0x3c & 0xf6
+        cpu.load_and_run(vec![0xa9, 0x01, 0xa2, 0x3c, 0x8b, 0xf6, 0x00]);
+        assert_eq!(cpu.reg_a, 0x24);
+        assert!(!cpu.status.contains(CpuFlags::ZERO));
+    }
+
+    #[test]
+    fn test_0xbb_las_absolute_y() {
+        let bus = Bus::new();
+        let mut cpu = CPU::new(bus);
+        cpu.memory_write_u8(0x0210, 0b1011_0111);
+        cpu.load_and_run(vec![0xa0, 0x10, 0xbb, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.reg_a, 0b1011_0101); // AND 0xfd
+        assert_eq!(cpu.reg_x, 0b1011_0101);
+        assert_eq!(cpu.reg_sp, 0b1011_0101);
+        assert!(cpu.status.contains(CpuFlags::NEGATIVE));
+    }
+
+    #[test]
+    fn test_0x9e_shx_absolute_y() {
+        let bus = Bus::new();
+        let mut cpu = CPU::new(bus);
+        // SHX $0200,Y stores X & 0x03
+        cpu.load_and_run(vec![0xa2, 0xff, 0xa0, 0x10, 0x9e, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.memory_read_u8(0x0210), 0x03);
+    }
+
+    #[test]
+    fn test_0x9c_shy_page_crossed() {
+        let bus = Bus::new();
+        let mut cpu = CPU::new(bus);
+        // SHY $02ff,X with X = 1 stores 0x01 & 0x03, which replaces the high byte of 0x0300
+        cpu.load_and_run(vec![0xa2, 0x01, 0xa0, 0x01, 0x9c, 0xff, 0x02, 0x00]);
+        assert_eq!(cpu.memory_read_u8(0x0100), 0x01);
+        assert_eq!(cpu.memory_read_u8(0x0300), 0x00);
+    }
+
+    #[test]
+    fn test_0x9b_tas_absolute_y() {
+        let bus = Bus::new();
+        let mut cpu = CPU::new(bus);
+        // SP = 0x33 & 0xf3, then stores SP & 0x03 at 0x0201
+        cpu.load_and_run(vec![0xa9, 0x33, 0xa2, 0xf3, 0xa0, 0x01, 0x9b, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.reg_sp, 0x33);
+        assert_eq!(cpu.memory_read_u8(0x0201), 0x03);
+    }
+
+    #[test]
+    fn test_jam() {
+        let bus = Bus::new();
+        let mut cpu = CPU::new(bus);
+        cpu.load_and_run(vec![0xe8, 0x02, 0xe8]); // INX, JAM, INX
+        assert!(cpu.is_jammed());
+        assert_eq!(cpu.reg_x, 1);
+        assert_eq!(cpu.reg_pc, 0x0601);
+        assert!(!cpu.step());
+        cpu.reset();
+        assert!(!cpu.is_jammed());
+    }
+
+    struct IrqMapper;
+
+    impl Mapper for IrqMapper {
+        fn cpu_read(&mut self, addr: u16) -> u8 {
+            match addr {
+                0xfffa => 0x20,
+                0xfffb => 0x06,
+                0xfffe => 0x10,
+                0xffff => 0x06,
+                _ => 0,
+            }
+        }
+        fn cpu_write(&mut self, _addr: u16, _data: u8) {}
+        fn ppu_read(&mut self, _addr: u16) -> u8 { 0 }
+        fn ppu_write(&mut self, _addr: u16, _data: u8) {}
+        fn mirroring(&self) -> Mirroring { Mirroring::VERTICAL }
+        fn irq(&self) -> bool { true }
+    }
+
+    #[test]
+    fn test_irq() {
+        let bus = Bus::new_with_mapper(Rc::new(RefCell::new(IrqMapper)));
+        let mut cpu = CPU::new(bus);
+        let mut program = vec![0x58, 0xea, 0x00]; // CLI, NOP, BRK
+        program.resize(0x10, 0x00);
+        program.extend([0xa9, 0x42, 0x00]); // 0x0610: LDA #$42, BRK
+        cpu.load_and_run(program);
+        assert_eq!(cpu.reg_a, 0x42);
+        assert!(cpu.status.contains(CpuFlags::INTERRUPT_DISABLE));
+        assert_eq!(cpu.memory_read_u8(0x01fd), 0x06); // return address 0x0601
+        assert_eq!(cpu.memory_read_u8(0x01fc), 0x01);
+    }
+
+    #[test]
+    fn test_brk() {
+        let bus = Bus::new_with_mapper(Rc::new(RefCell::new(IrqMapper)));
+        let mut cpu = CPU::new(bus);
+        let mut program = vec![0x00, 0xff, 0xa2, 0x07]; // BRK, padding, LDX #$07
+        program.resize(0x10, 0x00);
+        program.extend([0xa9, 0x42, 0x40]); // 0x0610: LDA #$42, RTI
+        cpu.load(program);
+        cpu.reset();
+        cpu.reg_pc = 0x0600;
+        let start = cpu.bus.cycles();
+        assert!(cpu.step());
+        assert_eq!(cpu.bus.cycles() - start, 7);
+        assert_eq!(cpu.reg_pc, 0x0610);
+        assert!(cpu.status.contains(CpuFlags::INTERRUPT_DISABLE));
+        assert_eq!(cpu.memory_read_u8(0x01fd), 0x06); // return address 0x0602
+        assert_eq!(cpu.memory_read_u8(0x01fc), 0x02);
+        assert_eq!(cpu.memory_read_u8(0x01fb), 0b0011_0100); // B flag set
+        cpu.step();
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.reg_a, 0x42);
+        assert_eq!(cpu.reg_x, 0x07);
+    }
+
+    #[test]
+    fn test_nmi_hijacks_brk() {
+        let bus = Bus::new_with_mapper(Rc::new(RefCell::new(IrqMapper)));
+        let mut cpu = CPU::new(bus);
+        let mut program = vec![0x00, 0x00]; // BRK
+        program.resize(0x20, 0x00);
+        program.push(0xea); // 0x0620: NOP
+        cpu.load(program);
+        cpu.reset();
+        cpu.memory_write_u8(0x2000, 0x80); // NMI on vblank
+        // vblank starts a few cycles into BRK
+        while !(cpu.bus.ppu().scanline() == 240 && cpu.bus.ppu().dot() >= 332) {
+            cpu.bus.tick(1);
+        }
+        cpu.reg_pc = 0x0600;
+        cpu.step();
+        assert_eq!(cpu.reg_pc, 0x0620);
+        assert_eq!(cpu.memory_read_u8(0x01fb) & 0b0001_0000, 0b0001_0000);
+        // the NMI is not taken a second time
+        cpu.step();
+        assert_eq!(cpu.reg_pc, 0x0621);
+    }
+
+    #[test]
+    fn test_stop() {
+        let mut cpu = CPU::new(Bus::new());
+        cpu.load(vec![0xe8, 0xe8, 0xe8]); // INX, INX, INX
+        cpu.reset();
+        cpu.reg_pc = 0x0600;
+        cpu.run_with_callback(|cpu| {
+            if cpu.reg_x == 2 {
+                cpu.stop();
+            }
+        });
+        assert_eq!(cpu.reg_x, 2);
+        assert!(cpu.is_stopped());
+        assert!(!cpu.step());
+    }
+
+    fn cycles_of(program: Vec<u8>, index: u8) -> usize {
+        let mut cpu = CPU::new(Bus::new());
+        cpu.load(program);
+        cpu.reset();
+        cpu.reg_pc = 0x0600;
+        cpu.reg_x = index;
+        cpu.reg_y = index;
+        let start = cpu.bus.cycles();
+        cpu.step();
+        return cpu.bus.cycles() - start;
+    }
+
+    #[test]
+    fn test_page_cross_cycles() {
+        // LDA $10fe,X
+        assert_eq!(cycles_of(vec![0xbd, 0xfe, 0x10], 1), 4);
+        assert_eq!(cycles_of(vec![0xbd, 0xfe, 0x10], 2), 5);
+        // STA $10fe,X has no penalty
+        assert_eq!(cycles_of(vec![0x9d, 0xfe, 0x10], 2), 5);
+    }
+
+    #[test]
+    fn test_branch_cycles() {
+        // BNE with Z clear after reset: taken
+        assert_eq!(cycles_of(vec![0xd0, 0x10], 0), 3);
+        // BNE to 0x0602 - 0x10 = 0x05f2, a new page
+        assert_eq!(cycles_of(vec![0xd0, 0xf0], 0), 4);
+        // BEQ not taken
+        assert_eq!(cycles_of(vec![0xf0, 0x10], 0), 2);
+    }
+
+    #[test]
+    fn test_reset_cycles() {
+        let mut cpu = CPU::new(Bus::new());
+        cpu.reset();
+        assert_eq!(cpu.bus.cycles(), 7);
+        assert_eq!(cpu.bus.ppu().dot(), 21);
+    }
+
+    fn access_log_of(program: Vec<u8>, reg_x: u8) -> (CPU, Vec<BusAccess>) {
+        let mut cpu = CPU::new(Bus::new());
+        cpu.load(program);
+        cpu.reset();
+        cpu.reg_pc = 0x0600;
+        cpu.reg_x = reg_x;
+        cpu.bus.enable_access_log();
+        cpu.step();
+        let log = cpu.bus.take_access_log();
+        return (cpu, log);
+    }
+
+    #[test]
+    fn test_opcode_cycles_match_table() {
+        let conditional = [0x10, 0x30, 0x50, 0x70, 0x90, 0xb0, 0xd0, 0xf0];
+        for opcode in opcodes::CPU_OPCODES.iter() {
+            if opcode.code == 0x00 || conditional.contains(&opcode.code) {
+                continue;
+            }
+            // operands at 0x0210 / 0x10, indexed without crossing a page
+            let cycles = cycles_of(vec![opcode.code, 0x10, 0x02], 0);
+            assert_eq!(cycles, opcode.cycles as usize, "{:02x} {}", opcode.code, opcode.mnemonic);
+            if opcode.page_cross_penalty {
+                let cycles = cycles_of(vec![opcode.code, 0xff, 0x02], 1);
+                let expected = opcode.cycles as usize + 1;
+                // (indirect),Y crosses through the pointer, not the operand
+                if !matches!(opcode.mode, AddressingMode::Indirect_Y) {
+                    assert_eq!(cycles, expected, "{:02x} {}", opcode.code, opcode.mnemonic);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rmw_dummy_write() {
+        // INC $10
+        let mut cpu = CPU::new(Bus::new());
+        cpu.memory_write_u8(0x10, 0x41);
+        cpu.load(vec![0xe6, 0x10]);
+        cpu.reset();
+        cpu.reg_pc = 0x0600;
+        cpu.bus.enable_access_log();
+        cpu.step();
+        assert_eq!(cpu.bus.take_access_log(), vec![
+            BusAccess::Read(0x0600, 0xe6),
+            BusAccess::Read(0x0601, 0x10),
+            BusAccess::Read(0x0010, 0x41),
+            BusAccess::Write(0x0010, 0x41),
+            BusAccess::Write(0x0010, 0x42),
+        ]);
+    }
+
+    #[test]
+    fn test_indexed_dummy_reads() {
+        // LDA $10ff,X reads the unfixed address 0x1000 first
+        let (_, log) = access_log_of(vec![0xbd, 0xff, 0x10], 1);
+        assert_eq!(log, vec![
+            BusAccess::Read(0x0600, 0xbd),
+            BusAccess::Read(0x0601, 0xff),
+            BusAccess::Read(0x0602, 0x10),
+            BusAccess::Read(0x1000, 0x00),
+            BusAccess::Read(0x1100, 0x00),
+        ]);
+
+        // STA $0200,X always reads before writing
+        let (_, log) = access_log_of(vec![0x9d, 0x00, 0x02], 1);
+        assert_eq!(log, vec![
+            BusAccess::Read(0x0600, 0x9d),
+            BusAccess::Read(0x0601, 0x00),
+            BusAccess::Read(0x0602, 0x02),
+            BusAccess::Read(0x0201, 0x00),
+            BusAccess::Write(0x0201, 0x00),
+        ]);
+
+        // LDA $10,X reads the zero page base address
+        let (_, log) = access_log_of(vec![0xb5, 0x10], 1);
+        assert_eq!(log, vec![
+            BusAccess::Read(0x0600, 0xb5),
+            BusAccess::Read(0x0601, 0x10),
+            BusAccess::Read(0x0010, 0x00),
+            BusAccess::Read(0x0011, 0x00),
+        ]);
+    }
+
+    #[test]
+    fn test_jsr_rts_bus_cycles() {
+        let mut program = vec![0x20, 0x10, 0x06]; // JSR $0610
+        program.resize(0x10, 0x00);
+        program.push(0x60); // RTS
+        let (mut cpu, log) = access_log_of(program, 0);
+        assert_eq!(log, vec![
+            BusAccess::Read(0x0600, 0x20),
+            BusAccess::Read(0x0601, 0x10),
+            BusAccess::Read(0x01fd, 0x00),
+            BusAccess::Write(0x01fd, 0x06),
+            BusAccess::Write(0x01fc, 0x02),
+            BusAccess::Read(0x0602, 0x06),
+        ]);
+        cpu.step();
+        assert_eq!(cpu.bus.take_access_log(), vec![
+            BusAccess::Read(0x0610, 0x60),
+            BusAccess::Read(0x0611, 0x00),
+            BusAccess::Read(0x01fb, 0x00),
+            BusAccess::Read(0x01fc, 0x02),
+            BusAccess::Read(0x01fd, 0x06),
+            BusAccess::Read(0x0602, 0x06),
+        ]);
+        assert_eq!(cpu.reg_pc, 0x0603);
+    }
+
+    #[test]
+    fn test_branch_bus_cycles() {
+        // BNE -16 from 0x0602 to 0x05f2 reads 0x06f2 before fixing the page
+        let (cpu, log) = access_log_of(vec![0xd0, 0xf0], 0);
+        assert_eq!(log, vec![
+            BusAccess::Read(0x0600, 0xd0),
+            BusAccess::Read(0x0601, 0xf0),
+            BusAccess::Read(0x0602, 0x00),
+            BusAccess::Read(0x06f2, 0x00),
+        ]);
+        assert_eq!(cpu.reg_pc, 0x05f2);
+    }
+
+    #[test]
+    fn test_flat_ram() {
+        let mut ram = FlatRam::new();
+        // LDX #$05, DEX, BNE -3, BRK
+        ram.load(0x8000, &[0xa2, 0x05, 0xca, 0xd0, 0xfd, 0x00]);
+        ram.load(0xfffc, &[0x00, 0x80]);
+        let mut cpu = CPU::new(ram);
+        cpu.reset();
+        cpu.stop_on_brk = true;
+        cpu.run();
+        assert_eq!(cpu.reg_x, 0);
+        assert_eq!(cpu.reg_pc, 0x8006);
+        // reset 7, LDX 2, (DEX 2 + BNE 3) * 4, DEX 2 + BNE 2, BRK 1
+        assert_eq!(cpu.bus.cycles(), 7 + 2 + 5 * 4 + 4 + 1);
+    }
+
+    #[test]
+    fn test_irq_masked() {
+        let bus = Bus::new_with_mapper(Rc::new(RefCell::new(IrqMapper)));
+        let mut cpu = CPU::new(bus);
+        let mut program = vec![0xa9, 0x01, 0x00]; // LDA #$01, BRK
+        program.resize(0x10, 0x00);
+        program.extend([0xa9, 0x42, 0x00]);
+        cpu.load_and_run(program);
+        assert_eq!(cpu.reg_a, 0x01);
+    }
+
+    #[test]
+    fn test_handlers_match_opcodes() {
+        for code in 0 .. 256 {
+            // "nop_with_read_unofficial" -> "NOP", "*NOP" -> "NOP"
+            let handler = CPU::<FlatRam>::HANDLER_NAMES[code].split('_').next().unwrap().to_uppercase();
+            let mnemonic = opcodes::OPCODE_TABLE[code].mnemonic.trim_start_matches('*');
+            assert_eq!(handler, mnemonic, "opcode {:#04x}", code);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::joypad::JoypadButton;
+use crate::renderer::Frame;
+
+// host side of the emulator
+//
+// Nes::run calls these once per frame, between frames, so a frontend never
+// runs in the middle of CPU execution.
+pub trait Frontend {
+    // polled before each frame is emulated
+    fn input(&mut self) -> JoypadButton;
+
+    // receives the finished picture of each frame
+    fn video(&mut self, frame: &Frame);
+
+    // receives the audio samples produced during the frame
+    fn audio(&mut self, _samples: &[f32]) {}
+
+    // called after each frame, returning false stops Nes::run
+    fn end_frame(&mut self) -> bool {
+        return true;
+    }
+}
+
+// runs a fixed number of frames with the given buttons held, without any output.
+// an input script changes the held buttons at given frames
+pub struct HeadlessFrontend {
+    pub buttons: JoypadButton,
+    pub frames: usize,
+    frame_limit: usize,
+    script: Vec<(usize, JoypadButton)>,
+}
+
+impl HeadlessFrontend {
+    pub fn new(frame_limit: usize) -> Self {
+        HeadlessFrontend {
+            buttons: JoypadButton::empty(),
+            frames: 0,
+            frame_limit: frame_limit,
+            script: Vec::new(),
+        }
+    }
+
+    pub fn new_with_script(frame_limit: usize, script: Vec<(usize, JoypadButton)>) -> Self {
+        let mut frontend = HeadlessFrontend::new(frame_limit);
+        frontend.script = script;
+        frontend.script.sort_by_key(|(frame, _)| *frame);
+        frontend.script.reverse();
+        return frontend;
+    }
+}
+
+// one entry per line, "<frame> <buttons>", buttons separated by ','
+// (A, B, SELECT, START, UP, DOWN, LEFT, RIGHT) or '-' for none.
+// the buttons are held from that frame until the next entry.
+// empty lines and lines starting with '#' are ignored.
+pub fn parse_input_script(text: &str) -> Result<Vec<(usize, JoypadButton)>, String> {
+    let mut script = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let mut fields = line.split_whitespace();
+        let frame = fields.next().unwrap().parse::<usize>().map_err(|_| error("invalid frame"))?;
+        let mut buttons = JoypadButton::empty();
+        for name in fields.next().unwrap_or("-").split(',') {
+            buttons |= match name.to_uppercase().as_str() {
+                "A" => JoypadButton::BUTTON_A,
+                "B" => JoypadButton::BUTTON_B,
+                "SELECT" => JoypadButton::SELECT,
+                "START" => JoypadButton::START,
+                "UP" => JoypadButton::UP,
+                "DOWN" => JoypadButton::DOWN,
+                "LEFT" => JoypadButton::LEFT,
+                "RIGHT" => JoypadButton::RIGHT,
+                "-" => JoypadButton::empty(),
+                _ => return Err(error(&format!("unknown button {}", name))),
+            };
+        }
+        script.push((frame, buttons));
+    }
+    return Ok(script);
+}
+
+impl Frontend for HeadlessFrontend {
+    fn input(&mut self) -> JoypadButton {
+        while let Some((frame, buttons)) = self.script.last() {
+            if *frame > self.frames {
+                break;
+            }
+            self.buttons = *buttons;
+            self.script.pop();
+        }
+        return self.buttons;
+    }
+
+    fn video(&mut self, _frame: &Frame) {}
+
+    fn end_frame(&mut self) -> bool {
+        self.frames += 1;
+        return self.frames < self.frame_limit;
+    }
+}
+
+// wraps another frontend and records the input of every frame
+pub struct RecordingFrontend<F: Frontend> {
+    pub inner: F,
+    pub inputs: Vec<JoypadButton>,
+}
+
+impl<F: Frontend> RecordingFrontend<F> {
+    pub fn new(inner: F) -> Self {
+        RecordingFrontend {
+            inner: inner,
+            inputs: Vec::new(),
+        }
+    }
+}
+
+impl<F: Frontend> Frontend for RecordingFrontend<F> {
+    fn input(&mut self) -> JoypadButton {
+        let buttons = self.inner.input();
+        self.inputs.push(buttons);
+        return buttons;
+    }
+
+    fn video(&mut self, frame: &Frame) {
+        self.inner.video(frame);
+    }
+
+    fn audio(&mut self, samples: &[f32]) {
+        self.inner.audio(samples);
+    }
+
+    fn end_frame(&mut self) -> bool {
+        return self.inner.end_frame();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_headless_frame_limit() {
+        let mut frontend = HeadlessFrontend::new(3);
+        assert!(frontend.end_frame());
+        assert!(frontend.end_frame());
+        assert!(!frontend.end_frame());
+        assert_eq!(frontend.frames, 3);
+    }
+
+    #[test]
+    fn test_parse_input_script() {
+        let script = parse_input_script("# title\n0 -\n\n60 start\n90 A,Right\n").unwrap();
+        assert_eq!(script, vec![
+            (0, JoypadButton::empty()),
+            (60, JoypadButton::START),
+            (90, JoypadButton::BUTTON_A | JoypadButton::RIGHT),
+        ]);
+        assert_eq!(parse_input_script("x A").unwrap_err(), "line 1: invalid frame");
+        assert_eq!(parse_input_script("0 A\n1 C").unwrap_err(), "line 2: unknown button C");
+    }
+
+    #[test]
+    fn test_headless_script() {
+        let script = vec![(2, JoypadButton::START), (1, JoypadButton::BUTTON_A)];
+        let mut frontend = HeadlessFrontend::new_with_script(4, script);
+        let mut inputs = Vec::new();
+        loop {
+            inputs.push(frontend.input());
+            if !frontend.end_frame() {
+                break;
+            }
+        }
+        assert_eq!(inputs, vec![
+            JoypadButton::empty(),
+            JoypadButton::BUTTON_A,
+            JoypadButton::START,
+            JoypadButton::START,
+        ]);
+    }
+
+    #[test]
+    fn test_recording_inputs() {
+        let mut headless = HeadlessFrontend::new(2);
+        headless.buttons = JoypadButton::START;
+        let mut frontend = RecordingFrontend::new(headless);
+        assert_eq!(frontend.input(), JoypadButton::START);
+        frontend.inner.buttons = JoypadButton::BUTTON_A;
+        assert_eq!(frontend.input(), JoypadButton::BUTTON_A);
+        assert_eq!(frontend.inputs, vec![JoypadButton::START, JoypadButton::BUTTON_A]);
+    }
+}
@@ -49,6 +49,10 @@ impl Joypad {
     pub fn set_status(&mut self, button: JoypadButton, pressed: bool) {
         self.status.set(button, pressed);
     }
+
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        self.status = buttons;
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,16 @@
+pub mod bus;
+pub mod cpu;
+pub mod opcodes;
+pub mod rom;
+pub mod trace;
+pub mod ppu;
+pub mod apu;
+pub mod renderer;
+pub mod joypad;
+pub mod mapper;
+pub mod nes;
+pub mod frontend;
+pub mod audio;
+pub mod png;
+
+pub use nes::Nes;
@@ -1,31 +1,31 @@
-pub mod bus;
-pub mod cpu;
-pub mod opcodes;
-pub mod rom;
-pub mod trace;
-pub mod ppu;
-pub mod renderer;
-pub mod joypad;
-
-use bus::Memory;
-use ppu::NesPPU;
-use joypad::Joypad;
-
-#[macro_use]
-extern crate lazy_static;
+use nesemulator::bus;
+use nesemulator::cpu;
+use nesemulator::rom;
+use nesemulator::trace;
+use nesemulator::bus::Memory;
+use nesemulator::audio::{HighPass, RateControl, Resampler, RingBuffer};
+use nesemulator::frontend::{Frontend, HeadlessFrontend, parse_input_script};
+use nesemulator::joypad::JoypadButton;
+use nesemulator::renderer::Frame;
+use nesemulator::Nes;
+use nesemulator::nes::save_path;
+
 extern crate getopts;
 
 use std::env;
 use getopts::Options;
-use std::fs::{File, metadata};
-use std::io::Read;
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use rand::Rng;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::event::Event;
 use sdl2::EventPump;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
 use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
 
 fn handle_user_input(cpu: &mut cpu::CPU, event_pump: &mut EventPump) {
     for event in event_pump.poll_iter() {
@@ -81,12 +81,85 @@ fn read_screen_state(cpu: &mut cpu::CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool
     return update;
 }
 
-fn read_rom_file(filename: &String) -> Result<rom::Rom, String> {
-    let mut fp = File::open(filename).expect("unable to open.");
-    let fp_metadata = metadata(&filename).expect("unable to get metadata.");
-    let mut buffer = vec![0; fp_metadata.len() as usize];
-    fp.read(&mut buffer).expect("unable to read.");
-    return rom::Rom::new(&buffer);
+// pulls the queued samples on the SDL audio thread
+struct SdlAudio {
+    buffer: Arc<Mutex<RingBuffer>>,
+    volume: f32,
+}
+
+impl AudioCallback for SdlAudio {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.buffer.lock().unwrap().pop(out);
+        for sample in out.iter_mut() {
+            *sample *= self.volume;
+        }
+    }
+}
+
+struct SdlAudioOutput {
+    _device: AudioDevice<SdlAudio>,
+    buffer: Arc<Mutex<RingBuffer>>,
+    rate_control: RateControl,
+    resampler: Resampler,
+    high_pass: HighPass,
+}
+
+struct SdlFrontend<'a> {
+    canvas: Canvas<Window>,
+    texture: Texture<'a>,
+    event_pump: EventPump,
+    keys: HashMap<Keycode, JoypadButton>,
+    buttons: JoypadButton,
+    audio: Option<SdlAudioOutput>,
+    quit: bool,
+}
+
+impl Frontend for SdlFrontend<'_> {
+    fn input(&mut self) -> JoypadButton {
+        for event in self.event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } | Event::KeyDown {
+                    keycode: Some( Keycode::Escape ),
+                    ..
+                } => self.quit = true,
+
+                Event::KeyDown { keycode, .. } => {
+                    if let Some(key) = self.keys.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                        self.buttons.set(*key, true);
+                    }
+                }
+                Event::KeyUp { keycode, .. } => {
+                    if let Some(key) = self.keys.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                        self.buttons.set(*key, false);
+                    }
+                }
+
+                _ => {},
+            }
+        }
+        return self.buttons;
+    }
+
+    fn video(&mut self, frame: &Frame) {
+        self.texture.update(None, &frame.data, 256 * 3).unwrap();
+        self.canvas.copy(&self.texture, None, None).unwrap();
+        self.canvas.present();
+    }
+
+    fn audio(&mut self, samples: &[f32]) {
+        if let Some(output) = &mut self.audio {
+            let filtered: Vec<f32> = samples.iter().map(|s| output.high_pass.process(*s)).collect();
+            let buffered = output.buffer.lock().unwrap().len();
+            let resampled = output.resampler.process(&filtered, output.rate_control.ratio(buffered));
+            output.buffer.lock().unwrap().push(&resampled);
+        }
+    }
+
+    fn end_frame(&mut self) -> bool {
+        return !self.quit;
+    }
 }
 
 fn print_usage(program: &str, opts: Options) {
@@ -99,7 +172,16 @@ fn main() {
     let program = args[0].clone();
 
     let mut opts = Options::new();
-    opts.optopt("m", "", "MODE=<default|nestest|snaketest>", "MODE");
+    opts.optopt("m", "", "MODE=<default|nestest|snaketest|headless>", "MODE");
+    opts.optopt("", "frames", "headless: frames to run (default: 60)", "FRAMES");
+    opts.optopt("", "input", "headless: input script, lines of '<frame> <buttons>'", "FILE");
+    opts.optopt("", "png", "headless: write the last frame as PNG", "FILE");
+    opts.optopt("", "dump-ram", "headless: write the 2KB CPU RAM", "FILE");
+    opts.optopt("", "dump-vram", "headless: write the 2KB PPU VRAM", "FILE");
+    opts.optopt("", "dump-oam", "headless: write the 256 bytes OAM", "FILE");
+    opts.optflag("", "no-sprite-limit", "draw more than 8 sprites per scanline");
+    opts.optflag("", "mute", "disable audio output");
+    opts.optopt("", "volume", "audio volume 0-100 (default: 50)", "VOLUME");
     opts.optflag("h", "help", "print this help menu");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m },
@@ -115,25 +197,69 @@ fn main() {
     };
     let mode = matches.opt_str("m").unwrap_or("default".to_string());
     let rom_filename = matches.free[0].clone().to_string();
-    let rom = read_rom_file(&rom_filename).expect("failed to read ROM.");
+    let raw = std::fs::read(&rom_filename).expect("unable to read.");
 
     // 'nestest'
     if mode == "nestest" {
-        let bus = bus::Bus::new_with_rom(rom, move |_ppu: &NesPPU, _joypad: &mut joypad::Joypad|{});
+        let rom = rom::Rom::new(&raw).expect("failed to read ROM.");
+        let bus = bus::Bus::new_with_rom(rom).expect("failed to load ROM.");
         let mut cpu = cpu::CPU::new(bus);
         cpu.reset();
         cpu.reg_pc = 0xc000;
+        cpu.stop_on_brk = true;
         cpu.run_with_callback(move |cpu| {
             println!("{}", trace::trace(cpu));
         });
         return;
     }
 
+    // 'headless'
+    if mode == "headless" {
+        let frames = match matches.opt_str("frames").map(|f| f.parse::<usize>()) {
+            Some(Ok(frames)) if frames > 0 => frames,
+            Some(_) => {
+                eprintln!("--frames must be a number of at least 1");
+                print_usage(&program, opts);
+                std::process::exit(2);
+            },
+            None => 60,
+        };
+        let script = match matches.opt_str("input") {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path).expect("unable to read input script.");
+                parse_input_script(&text).expect("invalid input script.")
+            },
+            None => Vec::new(),
+        };
+        let mut nes = Nes::new();
+        nes.set_sprite_limit(!matches.opt_present("no-sprite-limit"));
+        nes.load_rom(&raw).expect("failed to load ROM.");
+        let mut frontend = HeadlessFrontend::new_with_script(frames, script);
+        nes.run(&mut frontend);
+        report_jam(&nes);
+
+        if let Some(path) = matches.opt_str("png") {
+            std::fs::write(&path, nes.frame_buffer().to_png()).expect("unable to write PNG.");
+        }
+        if let Some(path) = matches.opt_str("dump-ram") {
+            std::fs::write(&path, nes.ram()).expect("unable to write RAM dump.");
+        }
+        if let Some(path) = matches.opt_str("dump-vram") {
+            std::fs::write(&path, nes.ppu().vram).expect("unable to write VRAM dump.");
+        }
+        if let Some(path) = matches.opt_str("dump-oam") {
+            std::fs::write(&path, nes.ppu().oam_data).expect("unable to write OAM dump.");
+        }
+        return;
+    }
+
     // 'snaketest'
     if mode == "snaketest" {
-        let bus = bus::Bus::new_with_rom(rom, move |_ppu: &NesPPU, _joypad: &mut joypad::Joypad|{});
+        let rom = rom::Rom::new(&raw).expect("failed to read ROM.");
+        let bus = bus::Bus::new_with_rom(rom).expect("failed to load ROM.");
         let mut cpu = cpu::CPU::new(bus);
         cpu.reset();
+        cpu.stop_on_brk = true;
 
         //init SDL2
         let sdl_context = sdl2::init().unwrap();
@@ -150,7 +276,7 @@ fn main() {
         let mut texture = creator
                             .create_texture_target(PixelFormatEnum::RGB24, 32 ,32).unwrap();
 
-        let mut screen_state = [ 0 as u8; 32 * 3 *32];
+        let mut screen_state = [ 0u8; 32 * 3 *32];
         let mut rng = rand::thread_rng();
 
         cpu.run_with_callback(move |cpu| {
@@ -177,53 +303,76 @@ fn main() {
                     .position_centered()
                     .build().unwrap();
     let mut canvas = window.into_canvas().present_vsync().build().unwrap();
-    let mut event_pump = sdl_context.event_pump().unwrap();
+    let event_pump = sdl_context.event_pump().unwrap();
     canvas.set_scale(3.0, 3.0).unwrap();
 
     let creator = canvas.texture_creator();
-    let mut texture = creator
-                        .create_texture_target(PixelFormatEnum::RGB24, 256 ,240).unwrap();
+    let texture = creator
+                    .create_texture_target(PixelFormatEnum::RGB24, 256 ,240).unwrap();
 
-    let mut frame = renderer::Frame::new();
     let mut keys = HashMap::new();
-    keys.insert(Keycode::Down,  joypad::JoypadButton::DOWN);
-    keys.insert(Keycode::Up,    joypad::JoypadButton::UP);
-    keys.insert(Keycode::Right, joypad::JoypadButton::RIGHT);
-    keys.insert(Keycode::Left,  joypad::JoypadButton::LEFT);
-    keys.insert(Keycode::A,     joypad::JoypadButton::SELECT);
-    keys.insert(Keycode::S,     joypad::JoypadButton::START);
-    keys.insert(Keycode::Z,     joypad::JoypadButton::BUTTON_B);
-    keys.insert(Keycode::X,     joypad::JoypadButton::BUTTON_A);
-
-    let bus = bus::Bus::new_with_rom(rom, move |ppu: &NesPPU, joypad: &mut Joypad|{
-        renderer::render(ppu, &mut frame);
-        texture.update(None, &frame.data, 256 * 3).unwrap();
-        canvas.copy(&texture, None, None).unwrap();
-        canvas.present();
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } | Event::KeyDown {
-                    keycode: Some( Keycode::Escape ),
-                    ..
-                } => std::process::exit(0),
-
-                Event::KeyDown { keycode, .. } => {
-                    if let Some(key) = keys.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                        joypad.set_status(*key, true);
-                    }
-                }
-                Event::KeyUp { keycode, .. } => {
-                    if let Some(key) = keys.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                        joypad.set_status(*key, false);
-                    }
-                }
+    keys.insert(Keycode::Down,  JoypadButton::DOWN);
+    keys.insert(Keycode::Up,    JoypadButton::UP);
+    keys.insert(Keycode::Right, JoypadButton::RIGHT);
+    keys.insert(Keycode::Left,  JoypadButton::LEFT);
+    keys.insert(Keycode::A,     JoypadButton::SELECT);
+    keys.insert(Keycode::S,     JoypadButton::START);
+    keys.insert(Keycode::Z,     JoypadButton::BUTTON_B);
+    keys.insert(Keycode::X,     JoypadButton::BUTTON_A);
 
-                _ => {},
+    let mut nes = Nes::new();
+    nes.set_sprite_limit(!matches.opt_present("no-sprite-limit"));
+    let audio = if matches.opt_present("mute") {
+        None
+    } else {
+        let volume = match matches.opt_str("volume") {
+            Some(v) => v.parse::<f32>().expect("invalid volume.").clamp(0.0, 100.0) / 100.0,
+            None => 0.5,
+        };
+        let audio_subsystem = sdl_context.audio().unwrap();
+        let desired = AudioSpecDesired {
+            freq: Some(44_100),
+            channels: Some(1),
+            samples: Some(1024),
+        };
+        let buffer = Arc::new(Mutex::new(RingBuffer::new(44_100 / 2)));
+        let device = audio_subsystem.open_playback(None, &desired, |_spec| {
+            SdlAudio {
+                buffer: buffer.clone(),
+                volume: volume,
             }
-        }
+        }).unwrap();
+        let freq = device.spec().freq as usize;
+        nes.set_sample_rate(freq as f64);
+        device.resume();
+        Some(SdlAudioOutput {
+            _device: device,
+            buffer: buffer,
+            // keep about 3 frames queued, adjusting the rate by up to 0.5%
+            rate_control: RateControl::new(freq * 3 / 60, 0.005),
+            resampler: Resampler::new(),
+            high_pass: HighPass::new(),
+        })
+    };
 
-    });
-    let mut cpu = cpu::CPU::new(bus);
-    cpu.reset();
-    cpu.run();
+    let mut frontend = SdlFrontend {
+        canvas: canvas,
+        texture: texture,
+        event_pump: event_pump,
+        keys: keys,
+        buttons: JoypadButton::empty(),
+        audio: audio,
+        quit: false,
+    };
+    nes.load_rom(&raw).expect("failed to load ROM.");
+    nes.attach_save_file(&save_path(Path::new(&rom_filename))).expect("failed to read save file.");
+    nes.run(&mut frontend);
+    report_jam(&nes);
+}
+
+// the CPU halts on a JAM opcode, which usually means the game crashed
+fn report_jam(nes: &Nes) {
+    if nes.cpu().is_jammed() {
+        println!("CPU jammed at ${:04X}", nes.cpu().reg_pc);
+    }
 }
@@ -0,0 +1,246 @@
+use crate::mapper::{Chr, Mapper};
+use crate::rom::Mirroring;
+
+// discrete logic boards, a single latch written through ROM space
+//
+// mapper  2 UxROM:       xxxx PPPP  16KB PRG at $8000, last bank fixed at $C000
+// mapper  3 CNROM:       xxxx xxCC  8KB CHR
+// mapper  7 AxROM:       xxxM xPPP  32KB PRG, one-screen mirroring select
+// mapper 11 Color Dreams: CCCC xxPP  32KB PRG, 8KB CHR
+// mapper 34 BNROM:       PPPP PPPP  32KB PRG
+//           NINA-001:    $7FFD: 32KB PRG, $7FFE/$7FFF: 4KB CHR (when CHR is larger than 8KB)
+// mapper 66 GxROM:       xxPP xxCC  32KB PRG, 8KB CHR
+//
+// with bus conflicts, the ROM drives the data bus at the same time as the CPU,
+// so the latched value is the AND of both
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Board {
+    UxROM,
+    CNROM,
+    AxROM,
+    COLOR_DREAMS,
+    BNROM,
+    NINA_001,
+    GxROM,
+}
+
+pub struct Discrete {
+    board: Board,
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+    prg_banks: [usize; 2], // 16KB units
+    chr_banks: [usize; 2], // 4KB units
+    bus_conflicts: bool,
+}
+
+impl Discrete {
+    pub fn new(board: Board, prg_rom: Vec<u8>, chr: Chr, mirroring: Mirroring) -> Self {
+        let last = (prg_rom.len() / PRG_BANK_SIZE).max(1) - 1;
+        let mut discrete = Discrete {
+            board: board,
+            prg_rom: prg_rom,
+            chr: chr,
+            mirroring: mirroring,
+            prg_banks: [0, last],
+            chr_banks: [0, 1],
+            bus_conflicts: false,
+        };
+        discrete.bus_conflicts = !matches!(board, Board::AxROM | Board::NINA_001);
+        if board == Board::AxROM {
+            discrete.mirroring = Mirroring::SINGLE_SCREEN_LOWER;
+        }
+        if board != Board::UxROM && board != Board::CNROM {
+            discrete.select_prg_32k(0);
+        }
+        return discrete;
+    }
+
+    pub fn set_bus_conflicts(&mut self, enabled: bool) {
+        self.bus_conflicts = enabled;
+    }
+
+    fn select_prg_32k(&mut self, bank: usize) {
+        self.prg_banks = [bank * 2, bank * 2 + 1];
+    }
+
+    fn select_chr_8k(&mut self, bank: usize) {
+        self.chr_banks = [bank * 2, bank * 2 + 1];
+    }
+
+    fn latch(&mut self, data: u8) {
+        let data = data as usize;
+        match self.board {
+            Board::UxROM => self.prg_banks[0] = data,
+            Board::CNROM => self.select_chr_8k(data),
+            Board::AxROM => {
+                self.select_prg_32k(data & 0x07);
+                self.mirroring = if data & 0x10 == 0 { Mirroring::SINGLE_SCREEN_LOWER } else { Mirroring::SINGLE_SCREEN_UPPER };
+            },
+            Board::COLOR_DREAMS => {
+                self.select_prg_32k(data & 0x03);
+                self.select_chr_8k(data >> 4);
+            },
+            Board::BNROM => self.select_prg_32k(data),
+            Board::NINA_001 => {},
+            Board::GxROM => {
+                self.select_prg_32k((data >> 4) & 0x03);
+                self.select_chr_8k(data & 0x03);
+            },
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = (self.prg_rom.len() / PRG_BANK_SIZE).max(1);
+        let bank = self.prg_banks[((addr - 0x8000) as usize) / PRG_BANK_SIZE] % banks;
+        return bank * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1));
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let banks = (self.chr.size() / CHR_BANK_SIZE).max(1);
+        let bank = self.chr_banks[addr as usize / CHR_BANK_SIZE] % banks;
+        return bank * CHR_BANK_SIZE + (addr as usize & (CHR_BANK_SIZE - 1));
+    }
+}
+
+impl Mapper for Discrete {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000 ..= 0xFFFF => {
+                return self.prg_rom[self.prg_offset(addr)];
+            },
+            _ => {
+                println!("invalid read at {:04x}", addr);
+                return 0;
+            }
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match (self.board, addr) {
+            (Board::NINA_001, 0x7FFD) => self.select_prg_32k((data & 0x01) as usize),
+            (Board::NINA_001, 0x7FFE) => self.chr_banks[0] = (data & 0x0f) as usize,
+            (Board::NINA_001, 0x7FFF) => self.chr_banks[1] = (data & 0x0f) as usize,
+            (Board::NINA_001, _) => {},
+            (_, 0x6000 ..= 0x7FFF) => {}, // PRG RAM is on the bus
+            (_, 0x8000 ..= 0xFFFF) => {
+                let data = if self.bus_conflicts { data & self.cpu_read(addr) } else { data };
+                self.latch(data);
+            },
+            (_, _) => {
+                println!("invalid write at {:04x}", addr);
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        return self.chr.read(self.chr_offset(addr));
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        let offset = self.chr_offset(addr);
+        self.chr.write(offset, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn banked_rom(banks: usize, size: usize) -> Vec<u8> {
+        let mut rom = vec![0xff; banks * size];
+        for i in 0 .. banks {
+            rom[i * size] = i as u8;
+        }
+        return rom;
+    }
+
+    fn new_board(board: Board) -> Discrete {
+        let mut discrete = Discrete::new(board, banked_rom(8, PRG_BANK_SIZE), Chr::new(banked_rom(8, CHR_BANK_SIZE)), Mirroring::VERTICAL);
+        discrete.set_bus_conflicts(false);
+        return discrete;
+    }
+
+    #[test]
+    fn test_uxrom() {
+        let mut uxrom = new_board(Board::UxROM);
+        assert_eq!(uxrom.cpu_read(0xC000), 7);
+        uxrom.cpu_write(0x8000, 3);
+        assert_eq!(uxrom.cpu_read(0x8000), 3);
+        assert_eq!(uxrom.cpu_read(0xC000), 7);
+    }
+
+    #[test]
+    fn test_cnrom() {
+        let mut cnrom = new_board(Board::CNROM);
+        cnrom.cpu_write(0x8000, 2);
+        assert_eq!(cnrom.ppu_read(0x0000), 4);
+        assert_eq!(cnrom.ppu_read(0x1000), 5);
+        assert_eq!(cnrom.cpu_read(0x8000), 0);
+    }
+
+    #[test]
+    fn test_axrom() {
+        let mut axrom = new_board(Board::AxROM);
+        assert_eq!(axrom.mirroring(), Mirroring::SINGLE_SCREEN_LOWER);
+        axrom.cpu_write(0x8000, 0x12);
+        assert_eq!(axrom.cpu_read(0x8000), 4);
+        assert_eq!(axrom.cpu_read(0xC000), 5);
+        assert_eq!(axrom.mirroring(), Mirroring::SINGLE_SCREEN_UPPER);
+    }
+
+    #[test]
+    fn test_color_dreams() {
+        let mut color_dreams = new_board(Board::COLOR_DREAMS);
+        color_dreams.cpu_write(0x8000, 0x21);
+        assert_eq!(color_dreams.cpu_read(0x8000), 2);
+        assert_eq!(color_dreams.ppu_read(0x0000), 4);
+    }
+
+    #[test]
+    fn test_bnrom() {
+        let mut bnrom = new_board(Board::BNROM);
+        bnrom.cpu_write(0x8000, 3);
+        assert_eq!(bnrom.cpu_read(0x8000), 6);
+        assert_eq!(bnrom.cpu_read(0xC000), 7);
+    }
+
+    #[test]
+    fn test_nina_001() {
+        let mut nina = new_board(Board::NINA_001);
+        nina.cpu_write(0x7FFD, 1);
+        nina.cpu_write(0x7FFE, 3);
+        nina.cpu_write(0x7FFF, 6);
+        assert_eq!(nina.cpu_read(0x8000), 2);
+        assert_eq!(nina.ppu_read(0x0000), 3);
+        assert_eq!(nina.ppu_read(0x1000), 6);
+    }
+
+    #[test]
+    fn test_gxrom() {
+        let mut gxrom = new_board(Board::GxROM);
+        gxrom.cpu_write(0x8000, 0x13);
+        assert_eq!(gxrom.cpu_read(0x8000), 2);
+        assert_eq!(gxrom.ppu_read(0x0000), 6);
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        let mut uxrom = new_board(Board::UxROM);
+        uxrom.set_bus_conflicts(true);
+        // ROM at 0x8000 holds bank number 0, so the write is masked out
+        uxrom.cpu_write(0x8000, 3);
+        assert_eq!(uxrom.cpu_read(0x8000), 0);
+        // ROM at 0x8001 holds 0xff
+        uxrom.cpu_write(0x8001, 3);
+        assert_eq!(uxrom.cpu_read(0x8000), 3);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::mapper::{Chr, Mapper};
+use crate::rom::Mirroring;
+
+// mapper 1
+//
+// registers are written serially through a 5bit shift register, LSB first.
+// the 5th write selects the register by address. a write on the cycle right
+// after another one is ignored, so the double write of INC/DEC counts once
+//
+// 0x8000 - 0x9FFF: Control
+// 0xA000 - 0xBFFF: CHR bank 0
+// 0xC000 - 0xDFFF: CHR bank 1
+// 0xE000 - 0xFFFF: PRG bank
+//
+// Control
+// 4bit0
+// -----
+// CPPMM
+// |||||
+// |||++- Mirroring (0: one-screen, lower bank; 1: one-screen, upper bank;
+// |||               2: vertical; 3: horizontal)
+// |++--- PRG ROM bank mode (0, 1: switch 32KB at $8000, ignoring low bit of bank number;
+// |                         2: fix first bank at $8000 and switch 16KB bank at $C000;
+// |                         3: fix last bank at $C000 and switch 16KB bank at $8000)
+// +----- CHR ROM bank mode (0: switch 8KB at a time; 1: switch two separate 4KB banks)
+const SHIFT_RESET: u8 = 0b1_0000;
+pub const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+pub struct Mmc1 {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    shift: u8,
+    control: u8,
+    chr_bank0: u8,
+    chr_bank1: u8,
+    prg_bank: u8,
+    cpu_cycle: usize,
+    last_write_cycle: Option<usize>,
+}
+
+impl Mmc1 {
+    pub fn new(prg_rom: Vec<u8>, chr: Chr) -> Self {
+        Mmc1 {
+            prg_rom: prg_rom,
+            chr: chr,
+            shift: SHIFT_RESET,
+            control: 0x0c,
+            chr_bank0: 0,
+            chr_bank1: 0,
+            prg_bank: 0,
+            cpu_cycle: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000 ..= 0x9FFF => self.control = data,
+            0xA000 ..= 0xBFFF => self.chr_bank0 = data,
+            0xC000 ..= 0xDFFF => self.chr_bank1 = data,
+            _ => self.prg_bank = data,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = self.prg_rom.len() / PRG_BANK_SIZE;
+        // SUROM: CHR bank bit 4 selects the 256KB half of a 512KB PRG ROM
+        let outer = if banks > 16 { self.chr_bank0 as usize & 0x10 } else { 0 };
+        let bank = self.prg_bank as usize & 0x0f;
+        let last = (banks - 1) & 0x0f;
+        let index = match ((self.control >> 2) & 0x03, addr) {
+            (0 | 1, 0x8000 ..= 0xBFFF) => bank & 0x0e,
+            (0 | 1, _) => bank | 0x01,
+            (2, 0x8000 ..= 0xBFFF) => 0,
+            (2, _) => bank,
+            (_, 0x8000 ..= 0xBFFF) => bank,
+            (_, _) => last,
+        };
+        let index = (outer | index) % banks;
+        return index * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1));
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let banks = (self.chr.size() / CHR_BANK_SIZE).max(1);
+        let index = if self.control & 0x10 == 0 {
+            (self.chr_bank0 as usize & 0x1e) | (addr as usize / CHR_BANK_SIZE)
+        } else if addr < 0x1000 {
+            self.chr_bank0 as usize
+        } else {
+            self.chr_bank1 as usize
+        };
+        return (index % banks) * CHR_BANK_SIZE + (addr as usize & (CHR_BANK_SIZE - 1));
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000 ..= 0xFFFF => {
+                return self.prg_rom[self.prg_offset(addr)];
+            },
+            _ => {
+                println!("invalid read at {:04x}", addr);
+                return 0;
+            }
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000 ..= 0xFFFF => {
+                let consecutive = self.last_write_cycle.is_some_and(|cycle| cycle + 1 == self.cpu_cycle);
+                self.last_write_cycle = Some(self.cpu_cycle);
+                if consecutive {
+                    return;
+                }
+                if data & 0x80 == 0x80 {
+                    self.shift = SHIFT_RESET;
+                    self.control |= 0x0c;
+                    return;
+                }
+                let full = self.shift & 0x01 == 0x01;
+                self.shift = (self.shift >> 1) | ((data & 0x01) << 4);
+                if full {
+                    self.write_register(addr, self.shift);
+                    self.shift = SHIFT_RESET;
+                }
+            },
+            0x6000 ..= 0x7FFF => {}, // PRG RAM is on the bus
+            _ => {
+                println!("invalid write at {:04x}", addr);
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        return self.chr.read(self.chr_offset(addr));
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        let offset = self.chr_offset(addr);
+        self.chr.write(offset, data);
+    }
+
+    fn set_cpu_cycle(&mut self, cycle: usize) {
+        self.cpu_cycle = cycle;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SINGLE_SCREEN_LOWER,
+            1 => Mirroring::SINGLE_SCREEN_UPPER,
+            2 => Mirroring::VERTICAL,
+            _ => Mirroring::HORIZONTAL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::{Bus, Memory};
+    use crate::cpu::CPU;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn write_serial(mmc1: &mut Mmc1, addr: u16, data: u8) {
+        for i in 0 .. 5 {
+            mmc1.cpu_write(addr, (data >> i) & 0x01);
+        }
+    }
+
+    fn banked_rom(banks: usize, size: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * size];
+        for i in 0 .. banks {
+            rom[i * size] = i as u8;
+        }
+        return rom;
+    }
+
+    #[test]
+    fn test_power_on_fixes_last_bank() {
+        let mut mmc1 = Mmc1::new(banked_rom(8, PRG_BANK_SIZE), Chr::new(banked_rom(2, CHR_BANK_SIZE)));
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+        assert_eq!(mmc1.cpu_read(0xC000), 7);
+    }
+
+    #[test]
+    fn test_serial_write() {
+        let mut mmc1 = Mmc1::new(banked_rom(8, PRG_BANK_SIZE), Chr::new(banked_rom(2, CHR_BANK_SIZE)));
+        write_serial(&mut mmc1, 0xE000, 0x03);
+        assert_eq!(mmc1.cpu_read(0x8000), 3);
+        assert_eq!(mmc1.cpu_read(0xC000), 7);
+    }
+
+    #[test]
+    fn test_reset_shift_register() {
+        let mut mmc1 = Mmc1::new(banked_rom(8, PRG_BANK_SIZE), Chr::new(banked_rom(2, CHR_BANK_SIZE)));
+        mmc1.cpu_write(0xE000, 0x01);
+        mmc1.cpu_write(0xE000, 0x01);
+        mmc1.cpu_write(0xE000, 0x80); // reset
+        write_serial(&mut mmc1, 0xE000, 0x02);
+        assert_eq!(mmc1.cpu_read(0x8000), 2);
+    }
+
+    #[test]
+    fn test_prg_32k_mode() {
+        let mut mmc1 = Mmc1::new(banked_rom(8, PRG_BANK_SIZE), Chr::new(banked_rom(2, CHR_BANK_SIZE)));
+        write_serial(&mut mmc1, 0x8000, 0b0_00_10);
+        write_serial(&mut mmc1, 0xE000, 0x05);
+        assert_eq!(mmc1.cpu_read(0x8000), 4);
+        assert_eq!(mmc1.cpu_read(0xC000), 5);
+    }
+
+    #[test]
+    fn test_prg_fix_first_mode() {
+        let mut mmc1 = Mmc1::new(banked_rom(8, PRG_BANK_SIZE), Chr::new(banked_rom(2, CHR_BANK_SIZE)));
+        write_serial(&mut mmc1, 0x8000, 0b0_10_10);
+        write_serial(&mut mmc1, 0xE000, 0x05);
+        assert_eq!(mmc1.cpu_read(0x8000), 0);
+        assert_eq!(mmc1.cpu_read(0xC000), 5);
+    }
+
+    #[test]
+    fn test_chr_8k_mode() {
+        let mut mmc1 = Mmc1::new(banked_rom(2, PRG_BANK_SIZE), Chr::new(banked_rom(8, CHR_BANK_SIZE)));
+        write_serial(&mut mmc1, 0x8000, 0b0_11_10);
+        write_serial(&mut mmc1, 0xA000, 0x03); // low bit ignored
+        assert_eq!(mmc1.ppu_read(0x0000), 2);
+        assert_eq!(mmc1.ppu_read(0x1000), 3);
+    }
+
+    #[test]
+    fn test_chr_4k_mode() {
+        let mut mmc1 = Mmc1::new(banked_rom(2, PRG_BANK_SIZE), Chr::new(banked_rom(8, CHR_BANK_SIZE)));
+        write_serial(&mut mmc1, 0x8000, 0b1_11_10);
+        write_serial(&mut mmc1, 0xA000, 0x05);
+        write_serial(&mut mmc1, 0xC000, 0x02);
+        assert_eq!(mmc1.ppu_read(0x0000), 5);
+        assert_eq!(mmc1.ppu_read(0x1000), 2);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        let mut mmc1 = Mmc1::new(banked_rom(2, PRG_BANK_SIZE), Chr::new(banked_rom(2, CHR_BANK_SIZE)));
+        write_serial(&mut mmc1, 0x8000, 0b0_11_00);
+        assert_eq!(mmc1.mirroring(), Mirroring::SINGLE_SCREEN_LOWER);
+        write_serial(&mut mmc1, 0x8000, 0b0_11_01);
+        assert_eq!(mmc1.mirroring(), Mirroring::SINGLE_SCREEN_UPPER);
+        write_serial(&mut mmc1, 0x8000, 0b0_11_10);
+        assert_eq!(mmc1.mirroring(), Mirroring::VERTICAL);
+        write_serial(&mut mmc1, 0x8000, 0b0_11_11);
+        assert_eq!(mmc1.mirroring(), Mirroring::HORIZONTAL);
+    }
+
+    #[test]
+    fn test_ignore_consecutive_write() {
+        let mmc1 = Rc::new(RefCell::new(Mmc1::new(vec![0xff; 2 * PRG_BANK_SIZE], Chr::new(vec![0; 0x2000]))));
+        let mut cpu = CPU::new(Bus::new_with_mapper(mmc1.clone()));
+        mmc1.borrow_mut().cpu_write(0x8000, 0x01);
+        // INC $8000 writes $FF (reset), then $00 on the next cycle
+        cpu.bus.memory_write_u8(0x0000, 0xee);
+        cpu.bus.memory_write_u8(0x0001, 0x00);
+        cpu.bus.memory_write_u8(0x0002, 0x80);
+        cpu.reg_pc = 0x0000;
+        cpu.step();
+        assert_eq!(cpu.reg_pc, 0x0003);
+        assert_eq!(mmc1.borrow().shift, SHIFT_RESET);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::mapper::{Chr, Mapper};
+use crate::rom::Mirroring;
+
+// mapper 4
+//
+// 0x8000 - 0x9FFF: Bank select (even), Bank data (odd)
+// 0xA000 - 0xBFFF: Mirroring (even), PRG RAM protect (odd, bit 7: enable, bit 6: deny writes)
+// 0xC000 - 0xDFFF: IRQ latch (even), IRQ reload (odd)
+// 0xE000 - 0xFFFF: IRQ disable (even), IRQ enable (odd)
+//
+// Bank select
+// 7  bit  0
+// ---- ----
+// CPMx xRRR
+// |||   |||
+// |||   +++- Specify which bank register to update on next write to Bank Data register
+// |||        (0-1: 2KB CHR banks, 2-5: 1KB CHR banks, 6-7: 8KB PRG banks)
+// ||+------- Nothing on the MMC3
+// |+-------- PRG ROM bank mode (0: $8000-$9FFF swappable, $C000-$DFFF fixed to second-last bank;
+// |                             1: $C000-$DFFF swappable, $8000-$9FFF fixed to second-last bank)
+// +--------- CHR A12 inversion (0: two 2KB banks at $0000-$0FFF, four 1KB banks at $1000-$1FFF;
+//                               1: two 2KB banks at $1000-$1FFF, four 1KB banks at $0000-$0FFF)
+//
+// the scanline counter is clocked by rising edges of PPU A12
+pub const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+pub struct Mmc3 {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    four_screen: bool,
+    mirroring: Mirroring,
+    bank_select: u8,
+    registers: [u8; 8],
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl Mmc3 {
+    pub fn new(prg_rom: Vec<u8>, chr: Chr, mirroring: Mirroring) -> Self {
+        Mmc3 {
+            prg_rom: prg_rom,
+            chr: chr,
+            four_screen: mirroring == Mirroring::FOUR_SCREEN,
+            mirroring: mirroring,
+            bank_select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            prg_ram_protect: 0x80,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let banks = self.prg_rom.len() / PRG_BANK_SIZE;
+        let second_last = banks - 2;
+        let prg_mode = self.bank_select & 0x40 == 0x40;
+        let index = match (prg_mode, addr) {
+            (false, 0x8000 ..= 0x9FFF) => self.registers[6] as usize,
+            (true,  0x8000 ..= 0x9FFF) => second_last,
+            (_,     0xA000 ..= 0xBFFF) => self.registers[7] as usize,
+            (false, 0xC000 ..= 0xDFFF) => second_last,
+            (true,  0xC000 ..= 0xDFFF) => self.registers[6] as usize,
+            (_, _) => banks - 1,
+        };
+        return (index % banks) * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1));
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let banks = (self.chr.size() / CHR_BANK_SIZE).max(1);
+        // A12 inversion swaps the 2KB half and the 1KB half
+        let slot = if self.bank_select & 0x80 == 0x80 { (addr ^ 0x1000) / 0x0400 } else { addr / 0x0400 };
+        let index = match slot {
+            0 => self.registers[0] & 0xfe,
+            1 => self.registers[0] | 0x01,
+            2 => self.registers[1] & 0xfe,
+            3 => self.registers[1] | 0x01,
+            _ => self.registers[(slot - 2) as usize],
+        } as usize;
+        return (index % banks) * CHR_BANK_SIZE + (addr as usize & (CHR_BANK_SIZE - 1));
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000 ..= 0xFFFF => {
+                return self.prg_rom[self.prg_offset(addr)];
+            },
+            _ => {
+                println!("invalid read at {:04x}", addr);
+                return 0;
+            }
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        let even = addr & 0x01 == 0;
+        match (addr, even) {
+            (0x8000 ..= 0x9FFF, true) => self.bank_select = data,
+            (0x8000 ..= 0x9FFF, false) => self.registers[(self.bank_select & 0x07) as usize] = data,
+            (0xA000 ..= 0xBFFF, true) => {
+                if !self.four_screen {
+                    self.mirroring = if data & 0x01 == 0 { Mirroring::VERTICAL } else { Mirroring::HORIZONTAL };
+                }
+            },
+            (0xA000 ..= 0xBFFF, false) => self.prg_ram_protect = data & 0xc0,
+            (0xC000 ..= 0xDFFF, true) => self.irq_latch = data,
+            (0xC000 ..= 0xDFFF, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            },
+            (0xE000 ..= 0xFFFF, true) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            },
+            (0xE000 ..= 0xFFFF, false) => self.irq_enabled = true,
+            (0x6000 ..= 0x7FFF, _) => {}, // PRG RAM is on the bus
+            (_, _) => {
+                println!("invalid write at {:04x}", addr);
+            }
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        return self.prg_ram_protect & 0x80 == 0x80;
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        return self.prg_ram_protect & 0x40 == 0;
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        return self.chr.read(self.chr_offset(addr));
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        let offset = self.chr_offset(addr);
+        self.chr.write(offset, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn irq(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn a12_rising_edge(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::{Bus, Memory};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn banked_rom(banks: usize, size: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * size];
+        for i in 0 .. banks {
+            rom[i * size] = i as u8;
+        }
+        return rom;
+    }
+
+    fn new_mmc3() -> Mmc3 {
+        return Mmc3::new(banked_rom(16, PRG_BANK_SIZE), Chr::new(banked_rom(64, CHR_BANK_SIZE)), Mirroring::VERTICAL);
+    }
+
+    #[test]
+    fn test_prg_mode_0() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0x8000, 6);
+        mmc3.cpu_write(0x8001, 3);
+        mmc3.cpu_write(0x8000, 7);
+        mmc3.cpu_write(0x8001, 5);
+        assert_eq!(mmc3.cpu_read(0x8000), 3);
+        assert_eq!(mmc3.cpu_read(0xA000), 5);
+        assert_eq!(mmc3.cpu_read(0xC000), 14);
+        assert_eq!(mmc3.cpu_read(0xE000), 15);
+    }
+
+    #[test]
+    fn test_prg_mode_1() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0x8000, 0x46);
+        mmc3.cpu_write(0x8001, 3);
+        assert_eq!(mmc3.cpu_read(0x8000), 14);
+        assert_eq!(mmc3.cpu_read(0xC000), 3);
+        assert_eq!(mmc3.cpu_read(0xE000), 15);
+    }
+
+    #[test]
+    fn test_chr_banks() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0x8000, 0);
+        mmc3.cpu_write(0x8001, 9); // 2KB bank, low bit ignored
+        mmc3.cpu_write(0x8000, 2);
+        mmc3.cpu_write(0x8001, 20);
+        assert_eq!(mmc3.ppu_read(0x0000), 8);
+        assert_eq!(mmc3.ppu_read(0x0400), 9);
+        assert_eq!(mmc3.ppu_read(0x1000), 20);
+
+        // A12 inversion
+        mmc3.cpu_write(0x8000, 0x80);
+        assert_eq!(mmc3.ppu_read(0x1000), 8);
+        assert_eq!(mmc3.ppu_read(0x0000), 20);
+    }
+
+    #[test]
+    fn test_mirroring() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0xA000, 1);
+        assert_eq!(mmc3.mirroring(), Mirroring::HORIZONTAL);
+        mmc3.cpu_write(0xA000, 0);
+        assert_eq!(mmc3.mirroring(), Mirroring::VERTICAL);
+    }
+
+    #[test]
+    fn test_irq_counter() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0xC000, 2); // latch
+        mmc3.cpu_write(0xC001, 0); // reload
+        mmc3.cpu_write(0xE001, 0); // enable
+
+        mmc3.a12_rising_edge(); // reload -> 2
+        assert!(!mmc3.irq());
+        mmc3.a12_rising_edge(); // 1
+        assert!(!mmc3.irq());
+        mmc3.a12_rising_edge(); // 0
+        assert!(mmc3.irq());
+
+        mmc3.cpu_write(0xE000, 0); // acknowledge
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn test_irq_disabled() {
+        let mut mmc3 = new_mmc3();
+        mmc3.cpu_write(0xC000, 0);
+        mmc3.cpu_write(0xC001, 0);
+        mmc3.a12_rising_edge();
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn test_prg_ram_protect() {
+        let mut bus = Bus::new_with_mapper(Rc::new(RefCell::new(new_mmc3())));
+        bus.memory_write_u8(0x6000, 0x11);
+        bus.memory_write_u8(0xA001, 0xc0); // enabled, write protected
+        bus.memory_write_u8(0x6000, 0x22);
+        assert_eq!(bus.memory_read_u8(0x6000), 0x11);
+
+        bus.memory_write_u8(0xA001, 0x00); // disabled
+        bus.memory_write_u8(0x6000, 0x33);
+        assert_eq!(bus.memory_read_u8(0x6000), 0x00);
+
+        bus.memory_write_u8(0xA001, 0x80);
+        assert_eq!(bus.memory_read_u8(0x6000), 0x11);
+        bus.memory_write_u8(0x6000, 0x44);
+        assert_eq!(bus.memory_read_u8(0x6000), 0x44);
+    }
+}
@@ -0,0 +1,267 @@
+pub mod nrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod discrete;
+
+use crate::rom::Mirroring;
+use crate::rom::Rom;
+use crate::rom::{CHR_ROM_PAGE_SIZE, PRG_ROM_PAGE_SIZE};
+use nrom::Nrom;
+use mmc1::Mmc1;
+use mmc3::Mmc3;
+use discrete::{Board, Discrete};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// cartridge board
+//
+// CPU side: 0x4020 - 0xFFFF
+// PPU side: 0x0000 - 0x1FFF (pattern tables)
+pub trait Mapper {
+    fn cpu_read(&mut self, addr: u16) -> u8;
+    fn cpu_write(&mut self, addr: u16, data: u8);
+    fn ppu_read(&mut self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, data: u8);
+    fn mirroring(&self) -> Mirroring;
+
+    // level of the cartridge IRQ line
+    fn irq(&self) -> bool {
+        return false;
+    }
+
+    // notified by the PPU when its address line A12 goes from low to high
+    fn a12_rising_edge(&mut self) {}
+
+    // PRG RAM at 0x6000 - 0x7FFF, some boards can disable or write protect it
+    fn prg_ram_enabled(&self) -> bool {
+        return true;
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        return true;
+    }
+
+    // notified by the bus with the CPU cycle of the cpu_write that follows
+    fn set_cpu_cycle(&mut self, _cycle: usize) {}
+}
+
+// pattern memory of the cartridge
+//
+// a header with 0 CHR banks means the board has CHR RAM instead (8KB unless
+// the NES 2.0 header says otherwise), which the game fills through PPUDATA
+pub const CHR_RAM_SIZE: usize = 0x2000;
+
+pub struct Chr {
+    data: Vec<u8>,
+    is_ram: bool,
+}
+
+impl Chr {
+    pub fn new(chr_rom: Vec<u8>) -> Self {
+        return Chr::new_with_ram_size(chr_rom, CHR_RAM_SIZE);
+    }
+
+    pub fn new_with_ram_size(chr_rom: Vec<u8>, chr_ram_size: usize) -> Self {
+        if chr_rom.is_empty() {
+            return Chr {
+                data: vec![0; chr_ram_size.max(CHR_RAM_SIZE)],
+                is_ram: true,
+            };
+        }
+        return Chr {
+            data: chr_rom,
+            is_ram: false,
+        };
+    }
+
+    pub fn size(&self) -> usize {
+        return self.data.len();
+    }
+
+    pub fn is_ram(&self) -> bool {
+        return self.is_ram;
+    }
+
+    pub fn read(&self, offset: usize) -> u8 {
+        return self.data[offset];
+    }
+
+    pub fn write(&mut self, offset: usize, data: u8) {
+        if !self.is_ram {
+            println!("unable to write PPU CHR_ROM for {:04x}", offset);
+            return;
+        }
+        self.data[offset] = data;
+    }
+}
+
+// the mapper is shared between the CPU bus and the PPU
+pub type SharedMapper = Rc<RefCell<dyn Mapper>>;
+
+pub fn new_mapper(rom: Rom) -> Result<SharedMapper, String> {
+    let chr_rom_size = rom.chr_rom.len();
+    // NES 2.0 headers can give sizes that are not whole banks
+    if chr_rom_size > 0 && chr_rom_size < CHR_ROM_PAGE_SIZE {
+        return Err(format!("CHR ROM of {} bytes is smaller than 8KB", chr_rom_size));
+    }
+    let chr = Chr::new_with_ram_size(rom.chr_rom, rom.chr_ram_size);
+    let board = match (rom.mapper, rom.submapper) {
+        (2, _) => Board::UxROM,
+        (3, _) => Board::CNROM,
+        (7, _) => Board::AxROM,
+        (11, _) => Board::COLOR_DREAMS,
+        (34, 1) => Board::NINA_001,
+        (34, 2) => Board::BNROM,
+        // NINA-001 has CHR ROM banks, BNROM has 8KB CHR RAM
+        (34, _) => if chr_rom_size > 0x2000 { Board::NINA_001 } else { Board::BNROM },
+        (66, _) => Board::GxROM,
+        (0, _) => {
+            check_prg_rom_banks(&rom.prg_rom)?;
+            return Ok(Rc::new(RefCell::new(Nrom::new(rom.prg_rom, chr, rom.screen_mirroring))));
+        },
+        (1, _) => {
+            // the last bank is fixed at power on
+            if rom.prg_rom.len() < mmc1::PRG_BANK_SIZE {
+                return Err(format!("MMC1 PRG ROM of {} bytes is smaller than a bank", rom.prg_rom.len()));
+            }
+            return Ok(Rc::new(RefCell::new(Mmc1::new(rom.prg_rom, chr))));
+        },
+        (4, _) => {
+            // the last two banks are fixed
+            if rom.prg_rom.len() < 2 * mmc3::PRG_BANK_SIZE {
+                return Err(format!("MMC3 PRG ROM of {} bytes is smaller than two banks", rom.prg_rom.len()));
+            }
+            return Ok(Rc::new(RefCell::new(Mmc3::new(rom.prg_rom, chr, rom.screen_mirroring))));
+        },
+        _ => return Err(format!("unsupported mapper {}", rom.mapper)),
+    };
+    check_prg_rom_banks(&rom.prg_rom)?;
+    let mut discrete = Discrete::new(board, rom.prg_rom, chr, rom.screen_mirroring);
+    // NES 2.0 submappers of UxROM, CNROM and AxROM: 1 without, 2 with bus conflicts
+    if matches!(rom.mapper, 2 | 3 | 7) {
+        match rom.submapper {
+            1 => discrete.set_bus_conflicts(false),
+            2 => discrete.set_bus_conflicts(true),
+            _ => {},
+        }
+    }
+    return Ok(Rc::new(RefCell::new(discrete)));
+}
+
+// NROM and the discrete boards map PRG ROM in whole 16KB banks
+fn check_prg_rom_banks(prg_rom: &[u8]) -> Result<(), String> {
+    if prg_rom.is_empty() || !prg_rom.len().is_multiple_of(PRG_ROM_PAGE_SIZE) {
+        return Err(format!("PRG ROM of {} bytes is not a multiple of 16KB", prg_rom.len()));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test::test_rom;
+
+    #[test]
+    fn test_new_mapper() {
+        let mapper = new_mapper(test_rom()).unwrap();
+        assert_eq!(mapper.borrow().mirroring(), Mirroring::VERTICAL);
+    }
+
+    #[test]
+    fn test_chr_ram() {
+        let mut chr = Chr::new(vec![]);
+        assert!(chr.is_ram());
+        assert_eq!(chr.size(), CHR_RAM_SIZE);
+        chr.write(0x1234, 0x55);
+        assert_eq!(chr.read(0x1234), 0x55);
+    }
+
+    #[test]
+    fn test_chr_rom_is_read_only() {
+        let mut chr = Chr::new(vec![0x11; 0x2000]);
+        assert!(!chr.is_ram());
+        chr.write(0x0000, 0x55);
+        assert_eq!(chr.read(0x0000), 0x11);
+    }
+
+    #[test]
+    fn test_new_discrete_mapper() {
+        let mut rom = test_rom();
+        rom.mapper = 7;
+        let mapper = new_mapper(rom).unwrap();
+        assert_eq!(mapper.borrow().mirroring(), Mirroring::SINGLE_SCREEN_LOWER);
+    }
+
+    #[test]
+    fn test_submapper_bus_conflicts() {
+        let mut prg_rom = vec![0; 4 * 0x4000];
+        for bank in 0 .. 4 {
+            prg_rom[bank * 0x4000] = bank as u8;
+        }
+        for (submapper, expected) in [(1, 3), (2, 0)] {
+            let mut rom = test_rom();
+            rom.mapper = 2;
+            rom.submapper = submapper;
+            rom.prg_rom = prg_rom.clone();
+            let mapper = new_mapper(rom).unwrap();
+            // ROM at 0x8000 holds 0, masking the value with bus conflicts
+            mapper.borrow_mut().cpu_write(0x8000, 3);
+            assert_eq!(mapper.borrow_mut().cpu_read(0x8000), expected);
+        }
+    }
+
+    #[test]
+    fn test_mmc1_prg_rom_too_small() {
+        let mut rom = test_rom();
+        rom.mapper = 1;
+        rom.prg_rom = vec![0; 0x2000];
+        match new_mapper(rom) {
+            Result::Ok(_) => panic!("unexpected MMC1 with 8KB PRG ROM"),
+            Result::Err(str) => assert_eq!(str, "MMC1 PRG ROM of 8192 bytes is smaller than a bank"),
+        }
+    }
+
+    #[test]
+    fn test_mmc3_prg_rom_too_small() {
+        let mut rom = test_rom();
+        rom.mapper = 4;
+        rom.prg_rom = vec![0; 0x2000];
+        match new_mapper(rom) {
+            Result::Ok(_) => panic!("unexpected MMC3 with 8KB PRG ROM"),
+            Result::Err(str) => assert_eq!(str, "MMC3 PRG ROM of 8192 bytes is smaller than two banks"),
+        }
+    }
+
+    #[test]
+    fn test_partial_prg_rom_bank() {
+        for mapper in [0, 2] {
+            let mut rom = test_rom();
+            rom.mapper = mapper;
+            rom.prg_rom = vec![0; 96];
+            match new_mapper(rom) {
+                Result::Ok(_) => panic!("unexpected mapper {} with 96 bytes of PRG ROM", mapper),
+                Result::Err(str) => assert_eq!(str, "PRG ROM of 96 bytes is not a multiple of 16KB"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_chr_rom_too_small() {
+        let mut rom = test_rom();
+        rom.chr_rom = vec![0; 16];
+        match new_mapper(rom) {
+            Result::Ok(_) => panic!("unexpected mapper with 16 bytes of CHR ROM"),
+            Result::Err(str) => assert_eq!(str, "CHR ROM of 16 bytes is smaller than 8KB"),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_mapper() {
+        let mut rom = test_rom();
+        rom.mapper = 255;
+        match new_mapper(rom) {
+            Result::Ok(_) => panic!("unexpected support mapper 255"),
+            Result::Err(str) => assert_eq!(str, "unsupported mapper 255"),
+        }
+    }
+}
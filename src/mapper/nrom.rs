@@ -0,0 +1,94 @@
+use crate::mapper::{Chr, Mapper};
+use crate::rom::Mirroring;
+
+// mapper 0
+//
+// 0x8000 - 0xBFFF: first 16KB of PRG ROM
+// 0xC000 - 0xFFFF: last 16KB of PRG ROM (mirror of the first for NROM-128)
+pub struct Nrom {
+    prg_rom: Vec<u8>,
+    chr: Chr,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(prg_rom: Vec<u8>, chr: Chr, mirroring: Mirroring) -> Self {
+        Nrom {
+            prg_rom: prg_rom,
+            chr: chr,
+            mirroring: mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x8000 ..= 0xFFFF => {
+                let mut fixed_addr = addr - 0x8000;
+                if self.prg_rom.len() == 0x4000 {
+                    fixed_addr &= 0x3FFF;
+                }
+                return self.prg_rom[fixed_addr as usize];
+            },
+            _ => {
+                println!("invalid read at {:04x}", addr);
+                return 0;
+            }
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, _data: u8) {
+        match addr {
+            0x8000 ..= 0xFFFF => {}, // no registers, the ROM ignores writes
+            0x6000 ..= 0x7FFF => {}, // PRG RAM is on the bus
+            _ => {
+                println!("invalid write at {:04x}", addr);
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        return self.chr.read(addr as usize);
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) {
+        self.chr.write(addr as usize, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nrom_128_mirror() {
+        let mut prg_rom = vec![0; 0x4000];
+        prg_rom[0x0010] = 0x66;
+        let mut nrom = Nrom::new(prg_rom, Chr::new(vec![0; 0x2000]), Mirroring::HORIZONTAL);
+        assert_eq!(nrom.cpu_read(0x8010), 0x66);
+        assert_eq!(nrom.cpu_read(0xC010), 0x66);
+    }
+
+    #[test]
+    fn test_nrom_256() {
+        let mut prg_rom = vec![0; 0x8000];
+        prg_rom[0x4010] = 0x77;
+        let mut nrom = Nrom::new(prg_rom, Chr::new(vec![0; 0x2000]), Mirroring::HORIZONTAL);
+        assert_eq!(nrom.cpu_read(0x8010), 0x00);
+        assert_eq!(nrom.cpu_read(0xC010), 0x77);
+    }
+
+    #[test]
+    fn test_ignore_rom_write() {
+        let mut prg_rom = vec![0; 0x8000];
+        prg_rom[0x0010] = 0x66;
+        let mut nrom = Nrom::new(prg_rom, Chr::new(vec![0; 0x2000]), Mirroring::HORIZONTAL);
+        nrom.cpu_write(0x8010, 0x77);
+        assert_eq!(nrom.cpu_read(0x8010), 0x66);
+    }
+}
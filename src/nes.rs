@@ -0,0 +1,297 @@
+use crate::apu::DEFAULT_SAMPLE_RATE;
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::frontend::Frontend;
+use crate::joypad::JoypadButton;
+use crate::ppu::NesPPU;
+use crate::renderer::Frame;
+use crate::rom::Rom;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// frames between flushes of the battery backed RAM, about 5 seconds
+const SAVE_FLUSH_INTERVAL: usize = 300;
+
+// the save file of a ROM is next to it, "game.nes" -> "game.sav"
+pub fn save_path(rom_path: &Path) -> PathBuf {
+    return rom_path.with_extension("sav");
+}
+
+// headless facade over the emulator core
+//
+// Nes owns the CPU, which owns the Bus and through it the PPU and the joypad.
+// it can be driven one instruction or one frame at a time, or by a Frontend.
+pub struct Nes {
+    cpu: CPU,
+    save_file: Option<PathBuf>,
+    sample_rate: f64,
+    sprite_limit: bool,
+}
+
+impl Nes {
+    pub fn new() -> Self {
+        Nes {
+            cpu: CPU::new(Bus::new()),
+            save_file: None,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sprite_limit: true,
+        }
+    }
+
+    pub fn load_rom(&mut self, raw: &[u8]) -> Result<(), String> {
+        let rom = Rom::new(raw)?;
+        let bus = Bus::new_with_rom(rom)?;
+        self.cpu = CPU::new(bus);
+        self.cpu.bus.apu_mut().set_sample_rate(self.sample_rate);
+        self.cpu.bus.ppu_mut().sprite_limit = self.sprite_limit;
+        self.cpu.reset();
+        self.save_file = None;
+        return Ok(());
+    }
+
+    // loads the battery backed RAM from the file if it exists,
+    // and keeps it up to date while running. ignored without a battery
+    pub fn attach_save_file(&mut self, path: &Path) -> io::Result<()> {
+        if !self.cpu.bus.has_battery() {
+            return Ok(());
+        }
+        match std::fs::read(path) {
+            Ok(data) => self.cpu.bus.load_prg_ram(&data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+        self.save_file = Some(path.to_path_buf());
+        return Ok(());
+    }
+
+    // writes the battery backed RAM if it has changed since the last flush
+    pub fn flush_save_file(&mut self) -> io::Result<()> {
+        if let Some(path) = &self.save_file {
+            if self.cpu.bus.poll_prg_ram_dirty() {
+                std::fs::write(path, self.cpu.bus.prg_ram())?;
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    // returns false once the CPU has been stopped
+    pub fn step_instruction(&mut self) -> bool {
+        return self.cpu.step();
+    }
+
+    // runs until the PPU enters vblank, when the frame is complete.
+    // audio samples not taken since the previous frame are dropped
+    pub fn run_frame(&mut self) -> bool {
+        self.cpu.bus.apu_mut().take_samples();
+        loop {
+            if !self.cpu.step() {
+                return false;
+            }
+            if self.cpu.bus.poll_frame_complete() {
+                break;
+            }
+        }
+        return true;
+    }
+
+    // drives the emulator with the frontend until it asks to stop
+    pub fn run(&mut self, frontend: &mut dyn Frontend) {
+        let mut frames = 0;
+        loop {
+            self.set_buttons(frontend.input());
+            let running = self.run_frame();
+            frontend.video(self.frame_buffer());
+            frontend.audio(&self.take_audio_samples());
+            if !running || !frontend.end_frame() {
+                break;
+            }
+            frames += 1;
+            if frames % SAVE_FLUSH_INTERVAL == 0 {
+                self.flush_save_file_or_warn();
+            }
+        }
+        self.flush_save_file_or_warn();
+    }
+
+    fn flush_save_file_or_warn(&mut self) {
+        if let Err(e) = self.flush_save_file() {
+            println!("failed to write save file: {}", e);
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.cpu.bus.apu_mut().set_sample_rate(sample_rate);
+    }
+
+    // the hardware draws at most 8 sprites per scanline, games flicker to show more
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.sprite_limit = enabled;
+        self.cpu.bus.ppu_mut().sprite_limit = enabled;
+    }
+
+    // mono samples, 0.0 - 1.0, produced since the frame started
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        return self.cpu.bus.apu_mut().take_samples();
+    }
+
+    pub fn frame_buffer(&self) -> &Frame {
+        return self.cpu.bus.ppu().frame();
+    }
+
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        self.cpu.bus.joypad_1().set_buttons(buttons);
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        return &self.cpu;
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        return &mut self.cpu;
+    }
+
+    pub fn ppu(&self) -> &NesPPU {
+        return self.cpu.bus.ppu();
+    }
+
+    // 2KB internal RAM of the CPU
+    pub fn ram(&self) -> &[u8] {
+        return self.cpu.bus.cpu_ram();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Memory;
+    use crate::frontend::HeadlessFrontend;
+    use crate::rom::test::create_test_rom_with_prg;
+
+    fn program_rom(program: &[u8]) -> Vec<u8> {
+        let mut prg_rom = vec![0xea; 0x8000];
+        prg_rom[.. program.len()].copy_from_slice(program);
+        // reset vector -> 0x8000
+        prg_rom[0x7ffc] = 0x00;
+        prg_rom[0x7ffd] = 0x80;
+        return create_test_rom_with_prg(prg_rom);
+    }
+
+    #[test]
+    fn test_load_rom_resets_cpu() {
+        let mut nes = Nes::new();
+        nes.load_rom(&program_rom(&[0x4c, 0x00, 0x80])).unwrap();
+        assert_eq!(nes.cpu().reg_pc, 0x8000);
+    }
+
+    #[test]
+    fn test_load_invalid_rom() {
+        let mut nes = Nes::new();
+        assert!(nes.load_rom(&[0; 32]).is_err());
+    }
+
+    #[test]
+    fn test_step_instruction() {
+        let mut nes = Nes::new();
+        // LDA #$42, STA $10, BRK
+        nes.load_rom(&program_rom(&[0xa9, 0x42, 0x85, 0x10, 0x00])).unwrap();
+        nes.cpu_mut().stop_on_brk = true;
+        assert!(nes.step_instruction());
+        assert_eq!(nes.cpu().reg_a, 0x42);
+        assert!(nes.step_instruction());
+        assert_eq!(nes.cpu_mut().memory_read_u8(0x10), 0x42);
+        assert!(!nes.step_instruction());
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut nes = Nes::new();
+        // JMP $8000
+        nes.load_rom(&program_rom(&[0x4c, 0x00, 0x80])).unwrap();
+        assert!(nes.run_frame());
+        assert!(nes.ppu().status.is_in_vblank());
+        assert_eq!(nes.frame_buffer().data.len(), 256 * 240 * 3);
+    }
+
+    #[test]
+    fn test_run_frame_jammed() {
+        let mut nes = Nes::new();
+        // NOP, JAM
+        nes.load_rom(&program_rom(&[0xea, 0x02])).unwrap();
+        assert!(!nes.run_frame());
+        assert!(nes.cpu().is_jammed());
+        assert_eq!(nes.cpu().reg_pc, 0x8001);
+    }
+
+    #[test]
+    fn test_set_buttons() {
+        let mut nes = Nes::new();
+        // strobe joypad, then read A and B into $10, $11
+        nes.load_rom(&program_rom(&[
+            0xa9, 0x01, 0x8d, 0x16, 0x40, // LDA #$01, STA $4016
+            0xa9, 0x00, 0x8d, 0x16, 0x40, // LDA #$00, STA $4016
+            0xad, 0x16, 0x40, 0x85, 0x10, // LDA $4016, STA $10
+            0xad, 0x16, 0x40, 0x85, 0x11, // LDA $4016, STA $11
+            0x00,
+        ])).unwrap();
+        nes.cpu_mut().stop_on_brk = true;
+        nes.set_buttons(JoypadButton::BUTTON_B | JoypadButton::START);
+        while nes.step_instruction() {}
+        assert_eq!(nes.cpu_mut().memory_read_u8(0x10), 0);
+        assert_eq!(nes.cpu_mut().memory_read_u8(0x11), 1);
+    }
+
+    #[test]
+    fn test_audio_samples() {
+        let mut nes = Nes::new();
+        nes.set_sample_rate(48_000.0);
+        // JMP $8000
+        nes.load_rom(&program_rom(&[0x4c, 0x00, 0x80])).unwrap();
+        assert!(nes.run_frame());
+        nes.run_frame();
+        // 48000Hz / 60.1fps
+        let samples = nes.take_audio_samples().len();
+        assert!((790 ..= 810).contains(&samples));
+    }
+
+    #[test]
+    fn test_save_file() {
+        // LDA #$42, STA $6000, BRK
+        let mut raw = program_rom(&[0xa9, 0x42, 0x8d, 0x00, 0x60, 0x00]);
+        raw[6] |= 0x02; // battery
+        let path = std::env::temp_dir().join(format!("nesemulator_test_{}.sav", std::process::id()));
+        std::fs::write(&path, [0x11, 0x22]).unwrap();
+
+        let mut nes = Nes::new();
+        nes.load_rom(&raw).unwrap();
+        nes.attach_save_file(&path).unwrap();
+        nes.cpu_mut().stop_on_brk = true;
+        assert_eq!(nes.cpu_mut().memory_read_u8(0x6001), 0x22);
+        while nes.step_instruction() {}
+        nes.flush_save_file().unwrap();
+
+        let saved = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.len(), 0x2000);
+        assert_eq!(&saved[.. 2], &[0x42, 0x22]);
+    }
+
+    #[test]
+    fn test_save_path() {
+        assert_eq!(save_path(Path::new("roms/game.nes")), PathBuf::from("roms/game.sav"));
+    }
+
+    #[test]
+    fn test_run_with_frontend() {
+        let mut nes = Nes::new();
+        // JMP $8000
+        nes.load_rom(&program_rom(&[0x4c, 0x00, 0x80])).unwrap();
+        let mut frontend = HeadlessFrontend::new(5);
+        nes.run(&mut frontend);
+        assert_eq!(frontend.frames, 5);
+    }
+}
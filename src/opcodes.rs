@@ -1,425 +1,469 @@
 use crate::cpu::AddressingMode;
-use std::collections::HashMap;
 
+#[derive(Clone, Copy)]
 pub struct OpCode {
     pub code: u8,
     pub mnemonic: &'static str,
     pub len: u8,
     pub cycles: u8,
     pub mode: AddressingMode,
+    pub page_cross_penalty: bool,
 }
 
 impl OpCode {
-    fn new(code: u8, mnemonic:&'static str, len: u8, cycles: u8, mode: AddressingMode) -> Self {
+    const fn new(code: u8, mnemonic:&'static str, len: u8, cycles: u8, mode: AddressingMode) -> Self {
         OpCode {
             code: code,
             mnemonic: mnemonic, 
             len: len,
             cycles: cycles,
             mode: mode,
+            page_cross_penalty: false,
         }
     }
+
+    // indexed reads take one more cycle when the address crosses a page
+    const fn page_cross(mut self) -> Self {
+        self.page_cross_penalty = true;
+        return self;
+    }
 }
 
-lazy_static! {
-    pub static ref CPU_OPCODES: Vec<OpCode> = vec![
-        // ADC -  Add with Carry
-        OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x6D, "ADC", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x7D, "ADC", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-        OpCode::new(0x79, "ADC", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-        OpCode::new(0x61, "ADC", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0x71, "ADC", 2, 5, AddressingMode::Indirect_Y), // cycle + 1 if page crossed
-
-        // AND - Logical AND
-        OpCode::new(0x29, "AND", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x2D, "AND", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x3D, "AND", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-        OpCode::new(0x39, "AND", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-        OpCode::new(0x21, "AND", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0x31, "AND", 2, 5, AddressingMode::Indirect_Y), // cycle + 1 if page crossed
-
-        // ASL - Arithmetic Shift Left
-        OpCode::new(0x0A, "ASL", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x0E, "ASL", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x1E, "ASL", 3, 7, AddressingMode::Absolute_X),
-
-        // BCC - Branch if Carry Clear
-        OpCode::new(0x90, "BCC", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // BCS - Branch if Carry Set
-        OpCode::new(0xB0, "BCS", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // BEQ - Branch if Equal
-        OpCode::new(0xF0, "BEQ", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // BIT - Bit Test
-        OpCode::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x2C, "BIT", 3, 4, AddressingMode::Absolute),
-
-        // BMI - Branch if Minus
-        OpCode::new(0x30, "BMI", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // BNE - Branch if Not Equal
-        OpCode::new(0xD0, "BNE", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // BPL - Branch if Positive
-        OpCode::new(0x10, "BPL", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // BRK - Force Interrupt
-        OpCode::new(0x00, "BRK", 1, 7, AddressingMode::NonAddressing),
-
-        // BVC - Branch if Overflow Clear
-        OpCode::new(0x50, "BVC", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // BVS - Branch if Overflow Set
-        OpCode::new(0x70, "BVS", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
-
-        // CLC - Clear Carry Flag
-        OpCode::new(0x18, "CLC", 1, 2, AddressingMode::NonAddressing),
-
-        // CLD - Clear Decimal Mode
-        OpCode::new(0xD8, "CLD", 1, 2, AddressingMode::NonAddressing),
-
-        // CLI - Clear Interrupt Disable
-        OpCode::new(0x58, "CLI", 1, 2, AddressingMode::NonAddressing),
-
-        // CLV - Clear Overflow Flag
-        OpCode::new(0xB8, "CLV", 1, 2, AddressingMode::NonAddressing),
-
-        // CMP - Compare
-        OpCode::new(0xC9, "CMP", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xC5, "CMP", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xD5, "CMP", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0xCD, "CMP", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xDD, "CMP", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-        OpCode::new(0xD9, "CMP", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-        OpCode::new(0xC1, "CMP", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0xD1, "CMP", 2, 5, AddressingMode::Indirect_Y), // cycle + 1 if page crossed
-
-        // CPX - Compare X Register
-        OpCode::new(0xE0, "CPX", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xE4, "CPX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xEC, "CPX", 3, 4, AddressingMode::Absolute),
-
-        // CPY - Compare Y Register
-        OpCode::new(0xC0, "CPY", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xC4, "CPY", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xCC, "CPY", 3, 4, AddressingMode::Absolute),
-
-        // DEC - Decrement Memory
-        OpCode::new(0xC6, "DEC", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0xD6, "DEC", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0xCE, "DEC", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0xDE, "DEC", 3, 7, AddressingMode::Absolute_X),
-
-        // DEX - Decrement X Register
-        OpCode::new(0xCA, "DEX", 1, 2, AddressingMode::NonAddressing),
-
-        // DEY - Decrement Y Register
-        OpCode::new(0x88, "DEY", 1, 2, AddressingMode::NonAddressing),
-
-        // EOR - Exclusive OR
-        OpCode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x4D, "EOR", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x5D, "EOR", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-        OpCode::new(0x59, "EOR", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-        OpCode::new(0x41, "EOR", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0x51, "EOR", 2, 5, AddressingMode::Indirect_Y), // cycle + 1 if page crossed
-
-        // INC - Increment Memory
-        OpCode::new(0xE6, "INC", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0xF6, "INC", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0xEE, "INC", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0xFE, "INC", 3, 7, AddressingMode::Absolute_X),
-
-        // INX - Increment X Register
-        OpCode::new(0xE8, "INX", 1, 2, AddressingMode::NonAddressing),
-
-        // INY - Increment Y Register
-        OpCode::new(0xC8, "INY", 1, 2, AddressingMode::NonAddressing),
-
-        // JMP - Jump
-        OpCode::new(0x4C, "JMP", 3, 3, AddressingMode::NonAddressing),
-        OpCode::new(0x6C, "JMP", 3, 3, AddressingMode::NonAddressing),
-
-        // JSR - Jump to Sub Routine
-        OpCode::new(0x20, "JSR", 3, 6, AddressingMode::NonAddressing),
-
-        // LDA - Load Accumulator
-        OpCode::new(0xA9, "LDA", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xA5, "LDA", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xB5, "LDA", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0xAD, "LDA", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xBD, "LDA", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-        OpCode::new(0xB9, "LDA", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-        OpCode::new(0xA1, "LDA", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0xB1, "LDA", 2, 5, AddressingMode::Indirect_Y), // cycle + 1 if page crossed
-
-        // LDX - Load X Register
-        OpCode::new(0xA2, "LDX", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xA6, "LDX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xB6, "LDX", 2, 4, AddressingMode::ZeroPage_Y),
-        OpCode::new(0xAE, "LDX", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xBE, "LDX", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-
-        // LDY - Load Y Register
-        OpCode::new(0xA0, "LDY", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xA4, "LDY", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xB4, "LDY", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0xAC, "LDY", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xBC, "LDY", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-
-        // LSR - Logical Shift Right
-        OpCode::new(0x4A, "LSR", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x4E, "LSR", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x5E, "LSR", 3, 7, AddressingMode::Absolute_X),
-
-        // NOP - No Operation
-        OpCode::new(0xEA, "NOP", 1, 2, AddressingMode::NonAddressing),
-
-        // ORA - Logical Inclusive OR
-        OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x0D, "ORA", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x1D, "ORA", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-        OpCode::new(0x19, "ORA", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-        OpCode::new(0x01, "ORA", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0x11, "ORA", 2, 5, AddressingMode::Indirect_Y), // cycle + 1 if page crossed
-
-        // PHA - Push Accumulator
-        OpCode::new(0x48, "PHA", 1, 3, AddressingMode::NonAddressing),
-
-        // PHP - Push Processor Status
-        OpCode::new(0x08, "PHP", 1, 3, AddressingMode::NonAddressing),
-
-        // PLA - Pull Accumulator
-        OpCode::new(0x68, "PLA", 1, 4, AddressingMode::NonAddressing),
-
-        // PLP - Pull Processor Status
-        OpCode::new(0x28, "PLP", 1, 4, AddressingMode::NonAddressing),
-
-        // ROL - Rotate Left
-        OpCode::new(0x2A, "ROL", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x2E, "ROL", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x3E, "ROL", 3, 7, AddressingMode::Absolute_X),
-
-        // ROR - Rotate Right
-        OpCode::new(0x6A, "ROR", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x6E, "ROR", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x7E, "ROR", 3, 7, AddressingMode::Absolute_X),
-
-        // RTI - Return from Interrupt
-        OpCode::new(0x40, "RTI", 1, 6, AddressingMode::NonAddressing),
-
-        // RTS - Return from Subroutine
-        OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NonAddressing),
-
-        // SBC - Subtract with Carry
-        OpCode::new(0xE9, "SBC", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xE5, "SBC", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xF5, "SBC", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0xED, "SBC", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xFD, "SBC", 3, 4, AddressingMode::Absolute_X), // cycle + 1 if page crossed
-        OpCode::new(0xF9, "SBC", 3, 4, AddressingMode::Absolute_Y), // cycle + 1 if page crossed
-        OpCode::new(0xE1, "SBC", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0xF1, "SBC", 2, 5, AddressingMode::Indirect_Y), // cycle + 1 if page crossed
-
-        // SEC - Set Carry Flag
-        OpCode::new(0x38, "SEC", 1, 2, AddressingMode::NonAddressing),
-
-        // SED - Set Decimal Flag
-        OpCode::new(0xF8, "SED", 1, 2, AddressingMode::NonAddressing),
-
-        // SEI - Set Interrupt Disable
-        OpCode::new(0x78, "SEI", 1, 2, AddressingMode::NonAddressing),
-
-        // STA - Store Accumulator
-        OpCode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x8d, "STA", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x9d, "STA", 3, 5, AddressingMode::Absolute_X),
-        OpCode::new(0x99, "STA", 3, 5, AddressingMode::Absolute_Y),
-        OpCode::new(0x81, "STA", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0x91, "STA", 2, 6, AddressingMode::Indirect_Y),
-
-        // STX - Store X Register
-        OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPage_Y),
-        OpCode::new(0x8E, "STX", 3, 4, AddressingMode::Absolute),
-
-        // STY - Store Y Register
-        OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x8C, "STY", 3, 4, AddressingMode::Absolute),
-
-        // TAX - Transfer Accumulator to X
-        OpCode::new(0xAA, "TAX", 1, 2, AddressingMode::NonAddressing),
-
-        // TAY - Transfer Accumulator to Y
-        OpCode::new(0xA8, "TAY", 1, 2, AddressingMode::NonAddressing),
-
-        // TSX - Transfer Stack Pointer to X
-        OpCode::new(0xBA, "TSX", 1, 2, AddressingMode::NonAddressing),
-
-        // TXA - Transfer X to Accumulator
-        OpCode::new(0x8A, "TXA", 1, 2, AddressingMode::NonAddressing),
-
-        // TXS - Transfer X to Stack Pointer
-        OpCode::new(0x9A, "TXS", 1, 2, AddressingMode::NonAddressing),
-
-        // TYA - Transfer Y to Accumulator
-        OpCode::new(0x98, "TYA", 1, 2, AddressingMode::NonAddressing),
-
-        // ========== unofficial opcodes ==========
-        // ALR - AND Accumulator and Logical Shift Right
-        OpCode::new(0x4B, "*ALR", 2, 2, AddressingMode::Immediate),
-
-        // ANC - AND Accumulator and if NEGATIVE set CARRY
-        OpCode::new(0x0B, "*ANC", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x2B, "*ANC", 2, 2, AddressingMode::Immediate),
-
-        // ARR - AND Accumulator and Rotate Right, check bit 5 and 6
-        OpCode::new(0x6B, "*ARR", 2, 2, AddressingMode::Immediate),
-
-        // AXS - AND X Register with Accumulator and store in, subtract memory without borrow
-        OpCode::new(0xCB, "*AXS", 2, 2, AddressingMode::Immediate),
-
-        // LAX - Load Accumulator and X Register
-        OpCode::new(0xA7, "*LAX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0xB7, "*LAX", 2, 4, AddressingMode::ZeroPage_Y),
-        OpCode::new(0xAF, "*LAX", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0xBF, "*LAX", 3, 4, AddressingMode::Absolute_Y),
-        OpCode::new(0xA3, "*LAX", 2, 6, AddressingMode::Indirect_X),
-        OpCode::new(0xB3, "*LAX", 2, 5, AddressingMode::Indirect_Y),
-
-        // SAX - And with X register into memory
-        OpCode::new(0x87, "*SAX", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x97, "*SAX", 2, 4, AddressingMode::ZeroPage_Y),
-        OpCode::new(0x8F, "*SAX", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x83, "*SAX", 2, 6, AddressingMode::Indirect_X),
-
-        // DCP - Decrement Memory without borrow
-        OpCode::new(0xC7, "*DCP", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0xD7, "*DCP", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0xCF, "*DCP", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0xDF, "*DCP", 3, 7, AddressingMode::Absolute_X),
-        OpCode::new(0xDB, "*DCP", 3, 7, AddressingMode::Absolute_Y),
-        OpCode::new(0xC3, "*DCP", 2, 8, AddressingMode::Indirect_X),
-        OpCode::new(0xD3, "*DCP", 2, 8, AddressingMode::Indirect_Y),
-
-        // ISB - Increment memory and Subtract from Accumulator
-        OpCode::new(0xE7, "*ISB", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0xF7, "*ISB", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0xEF, "*ISB", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0xFF, "*ISB", 3, 7, AddressingMode::Absolute_X),
-        OpCode::new(0xFB, "*ISB", 3, 7, AddressingMode::Absolute_Y),
-        OpCode::new(0xE3, "*ISB", 2, 8, AddressingMode::Indirect_X),
-        OpCode::new(0xF3, "*ISB", 2, 8, AddressingMode::Indirect_Y),
-
-        // RLA - Rotate Left in memory and AND Accumulator
-        OpCode::new(0x27, "*RLA", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x37, "*RLA", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x2F, "*RLA", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x3F, "*RLA", 3, 7, AddressingMode::Absolute_X),
-        OpCode::new(0x3B, "*RLA", 3, 7, AddressingMode::Absolute_Y),
-        OpCode::new(0x23, "*RLA", 2, 8, AddressingMode::Indirect_X),
-        OpCode::new(0x33, "*RLA", 2, 8, AddressingMode::Indirect_Y),
-
-        // RRA - Rotate Right in memory and Add Accumulator
-        OpCode::new(0x67, "*RRA", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x77, "*RRA", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x6F, "*RRA", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x7F, "*RRA", 3, 7, AddressingMode::Absolute_X),
-        OpCode::new(0x7B, "*RRA", 3, 7, AddressingMode::Absolute_Y),
-        OpCode::new(0x63, "*RRA", 2, 8, AddressingMode::Indirect_X),
-        OpCode::new(0x73, "*RRA", 2, 8, AddressingMode::Indirect_Y),
-
-        // SLO - Shift Left in memory and OR Accumulator
-        OpCode::new(0x07, "*SLO", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x17, "*SLO", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x0F, "*SLO", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x1F, "*SLO", 3, 7, AddressingMode::Absolute_X),
-        OpCode::new(0x1B, "*SLO", 3, 7, AddressingMode::Absolute_Y),
-        OpCode::new(0x03, "*SLO", 2, 8, AddressingMode::Indirect_X),
-        OpCode::new(0x13, "*SLO", 2, 8, AddressingMode::Indirect_Y),
-
-        // SRE - Shift Right in memory and Exclusive OR Accumulator
-        OpCode::new(0x47, "*SRE", 2, 5, AddressingMode::ZeroPage),
-        OpCode::new(0x57, "*SRE", 2, 6, AddressingMode::ZeroPage_X),
-        OpCode::new(0x4F, "*SRE", 3, 6, AddressingMode::Absolute),
-        OpCode::new(0x5F, "*SRE", 3, 7, AddressingMode::Absolute_X),
-        OpCode::new(0x5B, "*SRE", 3, 7, AddressingMode::Absolute_Y),
-        OpCode::new(0x43, "*SRE", 2, 8, AddressingMode::Indirect_X),
-        OpCode::new(0x53, "*SRE", 2, 8, AddressingMode::Indirect_Y),
-
-        // SBC - Subtract with Carry
-        OpCode::new(0xEB, "*SBC", 2, 2, AddressingMode::Immediate),
-
-        // NOP
-        OpCode::new(0x80, "*NOP", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x82, "*NOP", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0x89, "*NOP", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xC2, "*NOP", 2, 2, AddressingMode::Immediate),
-        OpCode::new(0xE2, "*NOP", 2, 2, AddressingMode::Immediate),
-
-        OpCode::new(0x04, "*NOP", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x44, "*NOP", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x64, "*NOP", 2, 3, AddressingMode::ZeroPage),
-        OpCode::new(0x14, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x34, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x54, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x74, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0xD4, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0xF4, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
-        OpCode::new(0x0C, "*NOP", 3, 4, AddressingMode::Absolute),
-        OpCode::new(0x1C, "*NOP", 3, 4, AddressingMode::Absolute_X), // +1
-        OpCode::new(0x3C, "*NOP", 3, 4, AddressingMode::Absolute_X), // +1
-        OpCode::new(0x5C, "*NOP", 3, 4, AddressingMode::Absolute_X), // +1
-        OpCode::new(0x7C, "*NOP", 3, 4, AddressingMode::Absolute_X), // +1
-        OpCode::new(0xDC, "*NOP", 3, 4, AddressingMode::Absolute_X), // +1
-        OpCode::new(0xFC, "*NOP", 3, 4, AddressingMode::Absolute_X), // +1
-
-        OpCode::new(0x02, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x12, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x22, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x32, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x42, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x52, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x62, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x72, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x92, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0xB2, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0xD2, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0xF2, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x1A, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x3A, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x5A, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0x7A, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0xDA, "*NOP", 1, 2, AddressingMode::NonAddressing),
-        OpCode::new(0xFA, "*NOP", 1, 2, AddressingMode::NonAddressing),
-    ];
-
-    pub static ref OPCODE_MAP: HashMap<u8, &'static OpCode> = {
-        let mut map = HashMap::new();
-        for opcode in &*CPU_OPCODES {
-            map.insert(opcode.code, opcode);
-        }
-        return map;
-    };
-}
\ No newline at end of file
+pub const CPU_OPCODES: &[OpCode] = &[
+    // ADC -  Add with Carry
+    OpCode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x6D, "ADC", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x7D, "ADC", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x79, "ADC", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x61, "ADC", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x71, "ADC", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // AND - Logical AND
+    OpCode::new(0x29, "AND", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x2D, "AND", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x3D, "AND", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x39, "AND", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x21, "AND", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x31, "AND", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // ASL - Arithmetic Shift Left
+    OpCode::new(0x0A, "ASL", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0E, "ASL", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x1E, "ASL", 3, 7, AddressingMode::Absolute_X),
+
+    // BCC - Branch if Carry Clear
+    OpCode::new(0x90, "BCC", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // BCS - Branch if Carry Set
+    OpCode::new(0xB0, "BCS", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // BEQ - Branch if Equal
+    OpCode::new(0xF0, "BEQ", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // BIT - Bit Test
+    OpCode::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x2C, "BIT", 3, 4, AddressingMode::Absolute),
+
+    // BMI - Branch if Minus
+    OpCode::new(0x30, "BMI", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // BNE - Branch if Not Equal
+    OpCode::new(0xD0, "BNE", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // BPL - Branch if Positive
+    OpCode::new(0x10, "BPL", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // BRK - Force Interrupt
+    OpCode::new(0x00, "BRK", 1, 7, AddressingMode::NonAddressing),
+
+    // BVC - Branch if Overflow Clear
+    OpCode::new(0x50, "BVC", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // BVS - Branch if Overflow Set
+    OpCode::new(0x70, "BVS", 2, 2, AddressingMode::NonAddressing), // cycle +1 if success, +2 if to new page
+
+    // CLC - Clear Carry Flag
+    OpCode::new(0x18, "CLC", 1, 2, AddressingMode::NonAddressing),
+
+    // CLD - Clear Decimal Mode
+    OpCode::new(0xD8, "CLD", 1, 2, AddressingMode::NonAddressing),
+
+    // CLI - Clear Interrupt Disable
+    OpCode::new(0x58, "CLI", 1, 2, AddressingMode::NonAddressing),
+
+    // CLV - Clear Overflow Flag
+    OpCode::new(0xB8, "CLV", 1, 2, AddressingMode::NonAddressing),
+
+    // CMP - Compare
+    OpCode::new(0xC9, "CMP", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xC5, "CMP", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xD5, "CMP", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xCD, "CMP", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xDD, "CMP", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xD9, "CMP", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xC1, "CMP", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xD1, "CMP", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // CPX - Compare X Register
+    OpCode::new(0xE0, "CPX", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xE4, "CPX", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xEC, "CPX", 3, 4, AddressingMode::Absolute),
+
+    // CPY - Compare Y Register
+    OpCode::new(0xC0, "CPY", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xC4, "CPY", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xCC, "CPY", 3, 4, AddressingMode::Absolute),
+
+    // DEC - Decrement Memory
+    OpCode::new(0xC6, "DEC", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xD6, "DEC", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xCE, "DEC", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xDE, "DEC", 3, 7, AddressingMode::Absolute_X),
+
+    // DEX - Decrement X Register
+    OpCode::new(0xCA, "DEX", 1, 2, AddressingMode::NonAddressing),
+
+    // DEY - Decrement Y Register
+    OpCode::new(0x88, "DEY", 1, 2, AddressingMode::NonAddressing),
+
+    // EOR - Exclusive OR
+    OpCode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x4D, "EOR", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x5D, "EOR", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x59, "EOR", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x41, "EOR", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x51, "EOR", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // INC - Increment Memory
+    OpCode::new(0xE6, "INC", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xF6, "INC", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xEE, "INC", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xFE, "INC", 3, 7, AddressingMode::Absolute_X),
+
+    // INX - Increment X Register
+    OpCode::new(0xE8, "INX", 1, 2, AddressingMode::NonAddressing),
+
+    // INY - Increment Y Register
+    OpCode::new(0xC8, "INY", 1, 2, AddressingMode::NonAddressing),
+
+    // JMP - Jump
+    OpCode::new(0x4C, "JMP", 3, 3, AddressingMode::NonAddressing),
+    OpCode::new(0x6C, "JMP", 3, 5, AddressingMode::NonAddressing),
+
+    // JSR - Jump to Sub Routine
+    OpCode::new(0x20, "JSR", 3, 6, AddressingMode::NonAddressing),
+
+    // LDA - Load Accumulator
+    OpCode::new(0xA9, "LDA", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xA5, "LDA", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xB5, "LDA", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xAD, "LDA", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xBD, "LDA", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xB9, "LDA", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xA1, "LDA", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xB1, "LDA", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // LDX - Load X Register
+    OpCode::new(0xA2, "LDX", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xA6, "LDX", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xB6, "LDX", 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0xAE, "LDX", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xBE, "LDX", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+
+    // LDY - Load Y Register
+    OpCode::new(0xA0, "LDY", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xA4, "LDY", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xB4, "LDY", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xAC, "LDY", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xBC, "LDY", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+
+    // LSR - Logical Shift Right
+    OpCode::new(0x4A, "LSR", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x4E, "LSR", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x5E, "LSR", 3, 7, AddressingMode::Absolute_X),
+
+    // NOP - No Operation
+    OpCode::new(0xEA, "NOP", 1, 2, AddressingMode::NonAddressing),
+
+    // ORA - Logical Inclusive OR
+    OpCode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0D, "ORA", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x1D, "ORA", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x19, "ORA", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x01, "ORA", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x11, "ORA", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // PHA - Push Accumulator
+    OpCode::new(0x48, "PHA", 1, 3, AddressingMode::NonAddressing),
+
+    // PHP - Push Processor Status
+    OpCode::new(0x08, "PHP", 1, 3, AddressingMode::NonAddressing),
+
+    // PLA - Pull Accumulator
+    OpCode::new(0x68, "PLA", 1, 4, AddressingMode::NonAddressing),
+
+    // PLP - Pull Processor Status
+    OpCode::new(0x28, "PLP", 1, 4, AddressingMode::NonAddressing),
+
+    // ROL - Rotate Left
+    OpCode::new(0x2A, "ROL", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x2E, "ROL", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x3E, "ROL", 3, 7, AddressingMode::Absolute_X),
+
+    // ROR - Rotate Right
+    OpCode::new(0x6A, "ROR", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x6E, "ROR", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x7E, "ROR", 3, 7, AddressingMode::Absolute_X),
+
+    // RTI - Return from Interrupt
+    OpCode::new(0x40, "RTI", 1, 6, AddressingMode::NonAddressing),
+
+    // RTS - Return from Subroutine
+    OpCode::new(0x60, "RTS", 1, 6, AddressingMode::NonAddressing),
+
+    // SBC - Subtract with Carry
+    OpCode::new(0xE9, "SBC", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xE5, "SBC", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xF5, "SBC", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xED, "SBC", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xFD, "SBC", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xF9, "SBC", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xE1, "SBC", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xF1, "SBC", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // SEC - Set Carry Flag
+    OpCode::new(0x38, "SEC", 1, 2, AddressingMode::NonAddressing),
+
+    // SED - Set Decimal Flag
+    OpCode::new(0xF8, "SED", 1, 2, AddressingMode::NonAddressing),
+
+    // SEI - Set Interrupt Disable
+    OpCode::new(0x78, "SEI", 1, 2, AddressingMode::NonAddressing),
+
+    // STA - Store Accumulator
+    OpCode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x8d, "STA", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x9d, "STA", 3, 5, AddressingMode::Absolute_X),
+    OpCode::new(0x99, "STA", 3, 5, AddressingMode::Absolute_Y),
+    OpCode::new(0x81, "STA", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0x91, "STA", 2, 6, AddressingMode::Indirect_Y),
+
+    // STX - Store X Register
+    OpCode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0x8E, "STX", 3, 4, AddressingMode::Absolute),
+
+    // STY - Store Y Register
+    OpCode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x8C, "STY", 3, 4, AddressingMode::Absolute),
+
+    // TAX - Transfer Accumulator to X
+    OpCode::new(0xAA, "TAX", 1, 2, AddressingMode::NonAddressing),
+
+    // TAY - Transfer Accumulator to Y
+    OpCode::new(0xA8, "TAY", 1, 2, AddressingMode::NonAddressing),
+
+    // TSX - Transfer Stack Pointer to X
+    OpCode::new(0xBA, "TSX", 1, 2, AddressingMode::NonAddressing),
+
+    // TXA - Transfer X to Accumulator
+    OpCode::new(0x8A, "TXA", 1, 2, AddressingMode::NonAddressing),
+
+    // TXS - Transfer X to Stack Pointer
+    OpCode::new(0x9A, "TXS", 1, 2, AddressingMode::NonAddressing),
+
+    // TYA - Transfer Y to Accumulator
+    OpCode::new(0x98, "TYA", 1, 2, AddressingMode::NonAddressing),
+
+    // ========== unofficial opcodes ==========
+    // ALR - AND Accumulator and Logical Shift Right
+    OpCode::new(0x4B, "*ALR", 2, 2, AddressingMode::Immediate),
+
+    // ANC - AND Accumulator and if NEGATIVE set CARRY
+    OpCode::new(0x0B, "*ANC", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x2B, "*ANC", 2, 2, AddressingMode::Immediate),
+
+    // ARR - AND Accumulator and Rotate Right, check bit 5 and 6
+    OpCode::new(0x6B, "*ARR", 2, 2, AddressingMode::Immediate),
+
+    // AXS - AND X Register with Accumulator and store in, subtract memory without borrow
+    OpCode::new(0xCB, "*AXS", 2, 2, AddressingMode::Immediate),
+
+    // LAX - Load Accumulator and X Register
+    OpCode::new(0xA7, "*LAX", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0xB7, "*LAX", 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0xAF, "*LAX", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0xBF, "*LAX", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xA3, "*LAX", 2, 6, AddressingMode::Indirect_X),
+    OpCode::new(0xB3, "*LAX", 2, 5, AddressingMode::Indirect_Y).page_cross(), // cycle + 1 if page crossed
+
+    // SAX - And with X register into memory
+    OpCode::new(0x87, "*SAX", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x97, "*SAX", 2, 4, AddressingMode::ZeroPage_Y),
+    OpCode::new(0x8F, "*SAX", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x83, "*SAX", 2, 6, AddressingMode::Indirect_X),
+
+    // DCP - Decrement Memory without borrow
+    OpCode::new(0xC7, "*DCP", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xD7, "*DCP", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xCF, "*DCP", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xDF, "*DCP", 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0xDB, "*DCP", 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0xC3, "*DCP", 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0xD3, "*DCP", 2, 8, AddressingMode::Indirect_Y),
+
+    // ISB - Increment memory and Subtract from Accumulator
+    OpCode::new(0xE7, "*ISB", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0xF7, "*ISB", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0xEF, "*ISB", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0xFF, "*ISB", 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0xFB, "*ISB", 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0xE3, "*ISB", 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0xF3, "*ISB", 2, 8, AddressingMode::Indirect_Y),
+
+    // RLA - Rotate Left in memory and AND Accumulator
+    OpCode::new(0x27, "*RLA", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x37, "*RLA", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x2F, "*RLA", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x3F, "*RLA", 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x3B, "*RLA", 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x23, "*RLA", 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x33, "*RLA", 2, 8, AddressingMode::Indirect_Y),
+
+    // RRA - Rotate Right in memory and Add Accumulator
+    OpCode::new(0x67, "*RRA", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x77, "*RRA", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x6F, "*RRA", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x7F, "*RRA", 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x7B, "*RRA", 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x63, "*RRA", 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x73, "*RRA", 2, 8, AddressingMode::Indirect_Y),
+
+    // SLO - Shift Left in memory and OR Accumulator
+    OpCode::new(0x07, "*SLO", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x17, "*SLO", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0F, "*SLO", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x1F, "*SLO", 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x1B, "*SLO", 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x03, "*SLO", 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x13, "*SLO", 2, 8, AddressingMode::Indirect_Y),
+
+    // SRE - Shift Right in memory and Exclusive OR Accumulator
+    OpCode::new(0x47, "*SRE", 2, 5, AddressingMode::ZeroPage),
+    OpCode::new(0x57, "*SRE", 2, 6, AddressingMode::ZeroPage_X),
+    OpCode::new(0x4F, "*SRE", 3, 6, AddressingMode::Absolute),
+    OpCode::new(0x5F, "*SRE", 3, 7, AddressingMode::Absolute_X),
+    OpCode::new(0x5B, "*SRE", 3, 7, AddressingMode::Absolute_Y),
+    OpCode::new(0x43, "*SRE", 2, 8, AddressingMode::Indirect_X),
+    OpCode::new(0x53, "*SRE", 2, 8, AddressingMode::Indirect_Y),
+
+    // SBC - Subtract with Carry
+    OpCode::new(0xEB, "*SBC", 2, 2, AddressingMode::Immediate),
+
+    // ========== unstable unofficial opcodes ==========
+    // XAA - OR Accumulator with the magic constant, AND X Register and memory
+    OpCode::new(0x8B, "*XAA", 2, 2, AddressingMode::Immediate),
+
+    // LXA - OR Accumulator with the magic constant, AND memory into Accumulator and X Register
+    OpCode::new(0xAB, "*LXA", 2, 2, AddressingMode::Immediate),
+
+    // LAS - AND memory with Stack Pointer into Accumulator, X Register and Stack Pointer
+    OpCode::new(0xBB, "*LAS", 3, 4, AddressingMode::Absolute_Y).page_cross(), // cycle + 1 if page crossed
+
+    // SHA - Store Accumulator AND X Register AND high byte of address + 1
+    OpCode::new(0x9F, "*SHA", 3, 5, AddressingMode::Absolute_Y),
+    OpCode::new(0x93, "*SHA", 2, 6, AddressingMode::Indirect_Y),
+
+    // SHX - Store X Register AND high byte of address + 1
+    OpCode::new(0x9E, "*SHX", 3, 5, AddressingMode::Absolute_Y),
+
+    // SHY - Store Y Register AND high byte of address + 1
+    OpCode::new(0x9C, "*SHY", 3, 5, AddressingMode::Absolute_X),
+
+    // TAS - Accumulator AND X Register into Stack Pointer, then SHA
+    OpCode::new(0x9B, "*TAS", 3, 5, AddressingMode::Absolute_Y),
+
+    // NOP
+    OpCode::new(0x80, "*NOP", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x82, "*NOP", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0x89, "*NOP", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xC2, "*NOP", 2, 2, AddressingMode::Immediate),
+    OpCode::new(0xE2, "*NOP", 2, 2, AddressingMode::Immediate),
+
+    OpCode::new(0x04, "*NOP", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x44, "*NOP", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x64, "*NOP", 2, 3, AddressingMode::ZeroPage),
+    OpCode::new(0x14, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x34, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x54, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x74, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xD4, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0xF4, "*NOP", 2, 4, AddressingMode::ZeroPage_X),
+    OpCode::new(0x0C, "*NOP", 3, 4, AddressingMode::Absolute),
+    OpCode::new(0x1C, "*NOP", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x3C, "*NOP", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x5C, "*NOP", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0x7C, "*NOP", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xDC, "*NOP", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+    OpCode::new(0xFC, "*NOP", 3, 4, AddressingMode::Absolute_X).page_cross(), // cycle + 1 if page crossed
+
+    // JAM - halts the CPU until reset
+    OpCode::new(0x02, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x12, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x22, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x32, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x42, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x52, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x62, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x72, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x92, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0xB2, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0xD2, "*JAM", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0xF2, "*JAM", 1, 2, AddressingMode::NonAddressing),
+
+    OpCode::new(0x1A, "*NOP", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x3A, "*NOP", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x5A, "*NOP", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0x7A, "*NOP", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0xDA, "*NOP", 1, 2, AddressingMode::NonAddressing),
+    OpCode::new(0xFA, "*NOP", 1, 2, AddressingMode::NonAddressing),
+];
+
+// indexed by the opcode byte
+pub static OPCODE_TABLE: [OpCode; 256] = opcode_table();
+
+const fn opcode_table() -> [OpCode; 256] {
+    let mut table = [CPU_OPCODES[0]; 256];
+    let mut found = [false; 256];
+    let mut i = 0;
+    while i < CPU_OPCODES.len() {
+        let opcode = CPU_OPCODES[i];
+        table[opcode.code as usize] = opcode;
+        found[opcode.code as usize] = true;
+        i += 1;
+    }
+    let mut code = 0;
+    while code < 256 {
+        assert!(found[code], "an opcode is missing from CPU_OPCODES");
+        code += 1;
+    }
+    return table;
+}
@@ -0,0 +1,81 @@
+// background tile data, fetched during the 8 dots before it is shifted out
+pub struct Background {
+    pub name_table_byte: u8,
+    pub attribute: u8,
+    pub pattern_low: u8,
+    pub pattern_high: u8,
+    shift_pattern_low: u16,
+    shift_pattern_high: u16,
+    shift_attribute_low: u16,
+    shift_attribute_high: u16,
+}
+
+impl Background {
+    pub fn new() -> Self {
+        Background {
+            name_table_byte: 0,
+            attribute: 0,
+            pattern_low: 0,
+            pattern_high: 0,
+            shift_pattern_low: 0,
+            shift_pattern_high: 0,
+            shift_attribute_low: 0,
+            shift_attribute_high: 0,
+        }
+    }
+
+    // moves the fetched tile into the low byte of the shifters
+    pub fn reload(&mut self) {
+        self.shift_pattern_low = (self.shift_pattern_low & 0xff00) | self.pattern_low as u16;
+        self.shift_pattern_high = (self.shift_pattern_high & 0xff00) | self.pattern_high as u16;
+        // the attribute latches feed the same 2 bits into every pixel of the tile
+        let attribute_low = if self.attribute & 0x01 == 0x01 { 0xff } else { 0x00 };
+        let attribute_high = if self.attribute & 0x02 == 0x02 { 0xff } else { 0x00 };
+        self.shift_attribute_low = (self.shift_attribute_low & 0xff00) | attribute_low;
+        self.shift_attribute_high = (self.shift_attribute_high & 0xff00) | attribute_high;
+    }
+
+    pub fn shift(&mut self) {
+        self.shift_pattern_low <<= 1;
+        self.shift_pattern_high <<= 1;
+        self.shift_attribute_low <<= 1;
+        self.shift_attribute_high <<= 1;
+    }
+
+    // (palette, value) of the current pixel, value 0 is transparent
+    pub fn pixel(&self, fine_x: u8) -> (u8, u8) {
+        let bit = 0x8000 >> fine_x;
+        let value = ((self.shift_pattern_high & bit != 0) as u8) << 1
+            | (self.shift_pattern_low & bit != 0) as u8;
+        let palette = ((self.shift_attribute_high & bit != 0) as u8) << 1
+            | (self.shift_attribute_low & bit != 0) as u8;
+        return (palette, value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shift_out_tiles() {
+        let mut background = Background::new();
+        background.pattern_low = 0b1000_0001;
+        background.pattern_high = 0b0000_0001;
+        background.attribute = 0b10;
+        background.reload();
+        for _ in 0 .. 8 {
+            background.shift();
+        }
+        background.pattern_low = 0;
+        background.pattern_high = 0xff;
+        background.attribute = 0b01;
+        background.reload();
+
+        assert_eq!(background.pixel(0), (2, 1));
+        assert_eq!(background.pixel(1), (2, 0));
+        assert_eq!(background.pixel(7), (2, 3));
+        background.shift();
+        assert_eq!(background.pixel(7), (1, 2));
+    }
+}
@@ -43,17 +43,6 @@ impl ControlRegister {
         }
     }
 
-    pub fn name_table_address(&self) -> u16 {
-        let value = self.bits & 0x03;
-        match value {
-            0x00 => { return 0x2000; },
-            0x01 => { return 0x2400; },
-            0x02 => { return 0x2800; },
-            0x03 => { return 0x2c00; },
-            _ => panic!("invalid name_table bits"),
-        }
-    }
-
     pub fn sprite_pattern_address(&self) -> u16 {
         if self.contains(ControlRegister::SPRITE_PATTERN_ADDRESS) {
             return 0x1000;
@@ -1,15 +1,22 @@
-pub mod address;
 pub mod control;
 pub mod status;
 pub mod scroll;
 pub mod mask;
+pub mod background;
+pub mod sprite;
 
+use crate::renderer::{Frame, SYSTEM_PALETTE};
 use crate::rom::Mirroring;
-use address::AddressRegister;
+use crate::mapper::{Chr, SharedMapper};
+use crate::mapper::nrom::Nrom;
+use std::cell::RefCell;
+use std::rc::Rc;
 use control::ControlRegister;
 use status::StatusRegister;
 use scroll::ScrollRegister;
 use mask::MaskRegister;
+use background::Background;
+use sprite::Sprite;
 
 pub const PPU_REG_CONTROLLER: u16  = 0x2000;
 pub const PPU_REG_MASK: u16        = 0x2001;
@@ -30,11 +37,13 @@ const PPU_VRAM_2ND: u16 = 0x3000;
 const PPU_VRAM_END: u16 = 0x3eff;
 const PPU_PALETTE_TABLE: u16 = 0x3f00;
 const PPU_PALETTE_TABLE_END: u16 = 0x3fff;
+#[allow(dead_code)]
 const PPU_MIRRORS: u16 = 0x4000;
+#[allow(dead_code)]
 const PPU_MIRRORS_END: u16 = 0xffff;
 
 pub struct NesPPU {
-    pub chr_rom: Vec<u8>,
+    mapper: SharedMapper,
     pub palette_table: [u8; 32],
     pub vram: [u8; 2048],
     pub oam_address: u8,
@@ -42,10 +51,17 @@ pub struct NesPPU {
     internal_data_buffer: u8,
     cycles: usize,
     scanline: u16,
+    a12: bool,
+    odd_frame: bool,
     pub nmi_interrupt: Option<u8>,
+    background: Background,
+    secondary_oam: [u8; 32],
+    sprites: Vec<Sprite>,
+    // false draws every sprite of a line instead of the first 8, which removes flicker
+    pub sprite_limit: bool,
+    frame: Frame,
 
     pub mirroring: Mirroring,
-    pub address: AddressRegister,
     pub control: ControlRegister,
     pub status: StatusRegister,
     pub scroll: ScrollRegister,
@@ -72,8 +88,14 @@ impl NesPPU {
     }
 
     pub fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
+        let mapper = Rc::new(RefCell::new(Nrom::new(vec![0; 16384], Chr::new(chr_rom), mirroring)));
+        return NesPPU::new_with_mapper(mapper);
+    }
+
+    pub fn new_with_mapper(mapper: SharedMapper) -> Self {
+        let mirroring = mapper.borrow().mirroring();
         NesPPU {
-            chr_rom: chr_rom,
+            mapper: mapper,
             palette_table: [0; 32],
             vram: [0; 2048],
             oam_address: 0,
@@ -81,10 +103,17 @@ impl NesPPU {
             internal_data_buffer: 0x00,
             cycles: 0,
             scanline: 0,
+            a12: false,
+            odd_frame: false,
             nmi_interrupt: None,
 
+            background: Background::new(),
+            secondary_oam: [0xff; 32],
+            sprites: Vec::new(),
+            sprite_limit: true,
+            frame: Frame::new(),
+
             mirroring: mirroring,
-            address: AddressRegister::new(),
             control: ControlRegister::new(),
             status: StatusRegister::new(),
             scroll: ScrollRegister::new(),
@@ -92,8 +121,280 @@ impl NesPPU {
         }
     }
 
+    // pattern table access through the cartridge
+    pub fn read_chr(&self, addr: u16) -> u8 {
+        return self.mapper.borrow_mut().ppu_read(addr);
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        self.mapper.borrow_mut().ppu_write(addr, data);
+    }
+
+    // tracks A12 of the PPU address bus, the mapper counts its rising edges
+    fn observe_address(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 == 0x1000;
+        if a12 && !self.a12 {
+            self.mapper.borrow_mut().a12_rising_edge();
+        }
+        self.a12 = a12;
+    }
+
+    fn is_rendering_enabled(&self) -> bool {
+        return self.mask.show_background() || self.mask.show_sprites();
+    }
+
+    fn is_rendering_line(&self) -> bool {
+        return (self.scanline < 240 || self.scanline == 261) && self.is_rendering_enabled();
+    }
+
+    // sprite patterns are fetched from dot 257, background patterns for the next line from dot 321
+    fn clock_fetch_address(&mut self, dot: usize) {
+        if dot == 257 {
+            let sprite_table = if self.control.sprite_size() == 16 { 0x1000 } else { self.control.sprite_pattern_address() };
+            self.observe_address(sprite_table);
+        }
+        if dot == 321 {
+            self.observe_address(self.control.background_pattern_address());
+        }
+    }
+
+    fn fetch_name_table_byte(&mut self) {
+        let addr = self.scroll.name_table_address();
+        self.background.name_table_byte = self.vram[self.mirror_vram_address(addr) as usize];
+    }
+
+    fn fetch_attribute(&mut self) {
+        let addr = self.scroll.attribute_address();
+        let attribute_byte = self.vram[self.mirror_vram_address(addr) as usize];
+        self.background.attribute = (attribute_byte >> self.scroll.attribute_shift()) & 0x03;
+    }
+
+    fn background_pattern_row(&self) -> u16 {
+        let tile = self.background.name_table_byte as u16;
+        return self.control.background_pattern_address() + tile * 16 + self.scroll.fine_y();
+    }
+
+    // background fetch pipeline, tiles are fetched one tile ahead of the
+    // shifters and the first two tiles of a line are fetched at the end of the previous one
+    fn clock_background(&mut self, dot: usize) {
+        if (2 ..= 257).contains(&dot) || (322 ..= 337).contains(&dot) {
+            self.background.shift();
+        }
+        if dot % 8 == 1 && ((9 ..= 257).contains(&dot) || (329 ..= 337).contains(&dot)) {
+            self.background.reload();
+        }
+        if (1 ..= 256).contains(&dot) || (321 ..= 336).contains(&dot) {
+            match (dot - 1) % 8 {
+                0 => self.fetch_name_table_byte(),
+                2 => self.fetch_attribute(),
+                4 => self.background.pattern_low = self.read_chr(self.background_pattern_row()),
+                6 => self.background.pattern_high = self.read_chr(self.background_pattern_row() + 8),
+                7 => self.scroll.increment_coarse_x(),
+                _ => {},
+            }
+        }
+        if dot == 256 {
+            self.scroll.increment_y();
+        }
+        if dot == 257 {
+            self.scroll.copy_horizontal();
+        }
+        if self.scanline == 261 && (280 ..= 304).contains(&dot) {
+            self.scroll.copy_vertical();
+        }
+    }
+
+    fn is_sprite_in_range(&self, y: u8) -> bool {
+        let row = (self.scanline as usize).wrapping_sub(y as usize);
+        return row < self.control.sprite_size() as usize;
+    }
+
+    // selects the sprites of the next scanline. the first 8 in range are copied
+    // to secondary OAM, then the rest of OAM is searched for an overflow
+    fn evaluate_sprites(&mut self) {
+        self.secondary_oam = [0xff; 32];
+        let mut selected = [0u8; 8];
+        let mut count = 0;
+        let mut n = 0;
+        while n < 64 && count < 8 {
+            if self.is_sprite_in_range(self.oam_data[n * 4]) {
+                self.secondary_oam[count * 4 .. count * 4 + 4].copy_from_slice(&self.oam_data[n * 4 .. n * 4 + 4]);
+                selected[count] = n as u8;
+                count += 1;
+            }
+            n += 1;
+        }
+
+        // the hardware also advances the byte index m when a sprite is out of range,
+        // so tiles, attributes and X positions are compared as Y coordinates
+        let mut m = 0;
+        let mut overflow = n;
+        while overflow < 64 {
+            if self.is_sprite_in_range(self.oam_data[overflow * 4 + m]) {
+                self.status.set_sprite_overflow(true);
+                break;
+            }
+            overflow += 1;
+            m = (m + 1) & 0x03;
+        }
+
+        for (i, &index) in selected[.. count].iter().enumerate() {
+            let mut entry = [0; 4];
+            entry.copy_from_slice(&self.secondary_oam[i * 4 .. i * 4 + 4]);
+            self.fetch_sprite(index, entry);
+        }
+        if !self.sprite_limit {
+            for index in n .. 64 {
+                if self.is_sprite_in_range(self.oam_data[index * 4]) {
+                    let mut entry = [0; 4];
+                    entry.copy_from_slice(&self.oam_data[index * 4 .. index * 4 + 4]);
+                    self.fetch_sprite(index as u8, entry);
+                }
+            }
+        }
+    }
+
+    // fetches the row of the sprite for the next scanline, entry is y, tile, attributes, x
+    fn fetch_sprite(&mut self, index: u8, entry: [u8; 4]) {
+        let height = self.control.sprite_size() as u16;
+        let attributes = entry[2];
+        let mut row = self.scanline - entry[0] as u16;
+        if attributes & 0x80 == 0x80 {
+            // flips both halves of 8x16 sprites
+            row = height - 1 - row;
+        }
+        let (table, mut tile) = if height == 16 {
+            // bit 0 of the tile index selects the pattern table of 8x16 sprites
+            ((entry[1] as u16 & 0x01) * 0x1000, entry[1] as u16 & 0xfe)
+        } else {
+            (self.control.sprite_pattern_address(), entry[1] as u16)
+        };
+        if row >= 8 {
+            tile += 1;
+            row -= 8;
+        }
+        let addr = table + tile * 16 + row;
+        let mut pattern_low = self.read_chr(addr);
+        let mut pattern_high = self.read_chr(addr + 8);
+        if attributes & 0x40 == 0x40 {
+            pattern_low = pattern_low.reverse_bits();
+            pattern_high = pattern_high.reverse_bits();
+        }
+        self.sprites.push(Sprite {
+            index: index,
+            x: entry[3],
+            palette: attributes & 0x03,
+            behind_background: attributes & 0x20 == 0x20,
+            pattern_low: pattern_low,
+            pattern_high: pattern_high,
+        });
+    }
+
+    // (index, palette, value, behind background) of the opaque sprite pixel with the
+    // lowest OAM index. it hides the sprites after it even when it is behind the background
+    fn sprite_pixel(&self, x: usize) -> Option<(u8, u8, u8, bool)> {
+        for sprite in self.sprites.iter() {
+            let value = sprite.pixel(x);
+            if value != 0 {
+                return Some((sprite.index, sprite.palette, value, sprite.behind_background));
+            }
+        }
+        return None;
+    }
+
+    fn color(&self, palette_index: usize) -> (u8, u8, u8) {
+        let mut color = self.palette_table[palette_index] & 0x3f;
+        if self.mask.is_grayscale() {
+            color &= 0x30;
+        }
+        return SYSTEM_PALETTE[color as usize];
+    }
+
+    fn render_pixel(&mut self, x: usize) {
+        let mut background = 0;
+        if self.mask.show_background() && (x >= 8 || self.mask.leftmost_8pixels_background()) {
+            let (palette, value) = self.background.pixel(self.scroll.fine_x);
+            if value != 0 {
+                background = palette as usize * 4 + value as usize;
+            }
+        }
+        let mut sprite = None;
+        if self.mask.show_sprites() && (x >= 8 || self.mask.leftmost_8pixels_sprite()) {
+            sprite = self.sprite_pixel(x);
+        }
+
+        let palette_index = match sprite {
+            Some((index, palette, value, behind_background)) => {
+                if index == 0 && background != 0 && x != 255 {
+                    self.status.set_sprite_zero_hit(true);
+                }
+                if behind_background && background != 0 {
+                    background
+                } else {
+                    0x10 + palette as usize * 4 + value as usize
+                }
+            },
+            None => background,
+        };
+        let rgb = self.color(palette_index);
+        self.frame.set_pixel(x, self.scanline as usize, rgb);
+    }
+
+    // advances one dot, returns true when entering vblank
+    fn step(&mut self) -> bool {
+        let dot = self.cycles;
+        let mut vblank = false;
+        if self.is_rendering_line() {
+            self.clock_background(dot);
+            self.clock_fetch_address(dot);
+        }
+        if dot == 257 {
+            self.sprites.clear();
+            if self.scanline < 240 && self.is_rendering_enabled() {
+                self.evaluate_sprites();
+            }
+        }
+        if self.scanline < 240 && (1 ..= 256).contains(&dot) {
+            self.render_pixel(dot - 1);
+        }
+        if self.scanline == 241 && dot == 1 {
+            self.status.set_vblank_status(true);
+            if self.control.generate_vblank_nmi() {
+                self.nmi_interrupt = Some(1);
+            }
+            vblank = true;
+        }
+        if self.scanline == 261 && dot == 1 {
+            self.nmi_interrupt = None;
+            self.status.set_vblank_status(false);
+            self.status.set_sprite_zero_hit(false);
+            self.status.set_sprite_overflow(false);
+        }
+
+        self.cycles += 1;
+        // the last dot of the pre-render line is skipped on odd frames while rendering
+        if self.scanline == 261 && self.cycles == 340 && self.odd_frame && self.is_rendering_enabled() {
+            self.cycles = 341;
+        }
+        if self.cycles >= 341 {
+            self.cycles = 0;
+            self.scanline += 1;
+            if self.scanline >= 262 {
+                self.scanline = 0;
+                self.odd_frame = !self.odd_frame;
+            }
+        }
+        return vblank;
+    }
+
     fn increment_vram_address(&mut self) {
-        self.address.increment(self.control.vram_address_increment());
+        if self.is_rendering_line() {
+            // $2007 accesses while rendering clock both scroll increments at once
+            self.scroll.increment_coarse_x();
+            self.scroll.increment_y();
+        } else {
+            self.scroll.increment(self.control.vram_address_increment());
+        }
     }
 
     fn mirror_vram_address(&mut self, addr: u16) -> u16 {
@@ -108,43 +409,37 @@ impl NesPPU {
             // VERTICAL -> ABA'B'
             (Mirroring::VERTICAL, 2) => index - 0x0800,
             (Mirroring::VERTICAL, 3) => index - 0x0800,
+            // SINGLE_SCREEN -> AAAA
+            (Mirroring::SINGLE_SCREEN_LOWER, _) => index & 0x03ff,
+            (Mirroring::SINGLE_SCREEN_UPPER, _) => 0x0400 | (index & 0x03ff),
             // FOUR_SCREEN -> ABCD
             _ => index,
         }
     }
 
+    // returns true when the frame is complete (entering vblank)
     pub fn tick(&mut self, cycles: u8) -> bool {
-        self.cycles += cycles as usize;
-        if self.cycles >= 341 {
-            if self.is_sprite_zero_hit(self.cycles) {
-                self.status.set_sprite_zero_hit(true);
+        let mut frame_complete = false;
+        for _ in 0 .. cycles {
+            if self.step() {
+                frame_complete = true;
             }
+        }
+        return frame_complete;
+    }
 
-            self.cycles = self.cycles - 341;
-            self.scanline += 1;
+    pub fn scanline(&self) -> u16 {
+        return self.scanline;
+    }
 
-            if self.scanline == 241 {
-                self.status.set_vblank_status(true);
-                self.status.set_sprite_zero_hit(false);
-                if self.control.generate_vblank_nmi() {
-                    self.nmi_interrupt = Some(1);
-                }
-            }
-            if self.scanline >= 262 {
-                self.scanline = 0;
-                self.nmi_interrupt = None;
-                self.status.set_sprite_zero_hit(false);
-                self.status.set_vblank_status(false);
-                return true;
-            }
-        }
-        return false;
+    // PPU cycle within the scanline, 0 - 340
+    pub fn dot(&self) -> usize {
+        return self.cycles;
     }
 
-    fn is_sprite_zero_hit(&self, cycles: usize) -> bool {
-        let x = self.oam_data[3] as usize;
-        let y = self.oam_data[0] as usize;
-        return (y == self.scanline as usize) && x <= cycles && self.mask.show_sprites();
+    // the picture, filled in as the scanlines are drawn
+    pub fn frame(&self) -> &Frame {
+        return &self.frame;
     }
 
     pub fn poll_nmi(&mut self) -> Option<u8> {
@@ -152,18 +447,30 @@ impl NesPPU {
     }
 }
 
+// the 32 palette entries repeat up to $3FFF, and the backdrop entries
+// of the sprite palettes $3F10/$3F14/$3F18/$3F1C mirror $3F00/$3F04/$3F08/$3F0C
+fn palette_index(addr: u16) -> usize {
+    let index = addr & 0x1f;
+    return match index {
+        0x10 | 0x14 | 0x18 | 0x1c => (index - 0x10) as usize,
+        _ => index as usize,
+    };
+}
+
 impl PPU for NesPPU {
     fn write_address(&mut self, data: u8) {
-        self.address.update(data);
+        self.scroll.write_address(data);
+        self.observe_address(self.scroll.vram_address());
     }
 
     fn read_data(&mut self) -> u8 {
-        let addr = self.address.get();
+        let addr = self.scroll.vram_address();
+        self.observe_address(addr);
         self.increment_vram_address();
         match addr {
             PPU_CHR_ROM ..= PPU_CHR_ROM_END => {
                 let data = self.internal_data_buffer;
-                self.internal_data_buffer = self.chr_rom[addr as usize];
+                self.internal_data_buffer = self.read_chr(addr);
                 return data;
             },
             PPU_VRAM ..= PPU_VRAM_1ST_END => {
@@ -172,37 +479,34 @@ impl PPU for NesPPU {
                 return data;
             },
             PPU_VRAM_2ND ..= PPU_VRAM_END => {
-                panic!("invalid PPU VRAM address {:04x}", addr);
-            },
-            0x3f10 | 0x3f14 | 0x3f18 | 0x3f1c => {
-                let mirror_addr = addr - 0x10;
-                return self.palette_table[(mirror_addr - PPU_PALETTE_TABLE) as usize];
+                // mirror of $2000 - $2EFF
+                let data = self.internal_data_buffer;
+                self.internal_data_buffer = self.vram[self.mirror_vram_address(addr - 0x1000) as usize];
+                return data;
             },
             PPU_PALETTE_TABLE ..= PPU_PALETTE_TABLE_END => {
-                return self.palette_table[(addr - PPU_PALETTE_TABLE) as usize];
+                return self.palette_table[palette_index(addr)];
             },
             _ => panic!("invalid PPU address {:04x}", addr),
         }
     }
 
     fn write_data(&mut self, data: u8) {
-        let addr = self.address.get();
+        let addr = self.scroll.vram_address();
+        self.observe_address(addr);
         match addr {
             PPU_CHR_ROM ..= PPU_CHR_ROM_END => {
-                println!("unable to write PPU CHR_ROM for {:04x}", addr);
+                self.write_chr(addr, data);
             },
             PPU_VRAM ..= PPU_VRAM_1ST_END => {
                 self.vram[self.mirror_vram_address(addr) as usize] = data;
             },
             PPU_VRAM_2ND ..= PPU_VRAM_END => {
-                panic!("invalid PPU VRAM address {:04x}", addr);
-            },
-            0x3f10 | 0x3f14 | 0x3f18 | 0x3f1c => {
-                let mirror_addr = addr - 0x10;
-                self.palette_table[(mirror_addr - PPU_PALETTE_TABLE) as usize] = data;
+                // mirror of $2000 - $2EFF
+                self.vram[self.mirror_vram_address(addr - 0x1000) as usize] = data;
             },
             PPU_PALETTE_TABLE ..= PPU_PALETTE_TABLE_END => {
-                self.palette_table[(addr - PPU_PALETTE_TABLE) as usize] = data;
+                self.palette_table[palette_index(addr)] = data;
             },
             _ => panic!("invalid PPU address {:04x}", addr),
         }
@@ -212,6 +516,7 @@ impl PPU for NesPPU {
     fn write_control(&mut self, data: u8) {
         let before_nmi_status = self.control.generate_vblank_nmi();
         self.control.update(data);
+        self.scroll.write_control(data);
         if !before_nmi_status && self.control.generate_vblank_nmi() && self.status.is_in_vblank() {
             self.nmi_interrupt = Some(1);
         }
@@ -220,13 +525,12 @@ impl PPU for NesPPU {
     fn read_status(&mut self) -> u8 {
         let data = self.status.read();
         self.status.set_vblank_status(false);
-        self.address.reset();
-        self.scroll.reset();
+        self.scroll.reset_latch();
         return data;
     }
 
     fn write_scroll(&mut self, data: u8) {
-        self.scroll.update(data);
+        self.scroll.write_scroll(data);
     }
 
     fn write_mask(&mut self, data: u8) {
@@ -258,6 +562,7 @@ impl PPU for NesPPU {
 #[cfg(test)]
 pub mod test {
     use super::*;
+    use crate::mapper::Mapper;
 
     #[test]
     fn test_ppu_write_vram() {
@@ -268,6 +573,20 @@ pub mod test {
         assert_eq!(ppu.vram[0x0305], 0x66);
     }
 
+    #[test]
+    fn test_ppu_write_chr_ram() {
+        let mut ppu = NesPPU::new(vec![], Mirroring::HORIZONTAL);
+        ppu.write_address(0x10);
+        ppu.write_address(0x10);
+        ppu.write_data(0x66);
+        assert_eq!(ppu.read_chr(0x1010), 0x66);
+
+        ppu.write_address(0x10);
+        ppu.write_address(0x10);
+        ppu.read_data(); // load the buffer
+        assert_eq!(ppu.read_data(), 0x66);
+    }
+
     #[test]
     fn test_ppu_read_vram() {
         let mut ppu = NesPPU::new_empty_rom();
@@ -276,7 +595,7 @@ pub mod test {
         ppu.write_address(0x23);
         ppu.write_address(0x05);
         ppu.read_data(); // read, then address+=1
-        assert_eq!(ppu.address.get(), 0x2306);
+        assert_eq!(ppu.scroll.vram_address(), 0x2306);
         assert_eq!(ppu.read_data(), 0x66);
     }
 
@@ -356,6 +675,61 @@ pub mod test {
         assert_eq!(ppu.read_data(), 0x77); //read B' from B
     }
 
+    #[test]
+    fn test_vram_single_screen_mirror() {
+        let mut ppu = NesPPU::new(vec![0; 2048], Mirroring::SINGLE_SCREEN_UPPER);
+        ppu.write_address(0x28);
+        ppu.write_address(0x05);
+        ppu.write_data(0x66);
+        assert_eq!(ppu.vram[0x0405], 0x66);
+
+        ppu.write_address(0x20);
+        ppu.write_address(0x05);
+        ppu.read_data();
+        assert_eq!(ppu.read_data(), 0x66);
+    }
+
+    #[test]
+    fn test_palette_mirror() {
+        let mut ppu = NesPPU::new_empty_rom();
+        ppu.write_address(0x3f);
+        ppu.write_address(0x25);
+        ppu.write_data(0x11);
+        assert_eq!(ppu.palette_table[0x05], 0x11);
+
+        ppu.write_address(0x3f);
+        ppu.write_address(0x30);
+        ppu.write_data(0x22);
+        assert_eq!(ppu.palette_table[0x00], 0x22);
+
+        ppu.palette_table[0x06] = 0x33;
+        ppu.write_address(0x3f);
+        ppu.write_address(0x26);
+        assert_eq!(ppu.read_data(), 0x33);
+
+        ppu.write_address(0x3f);
+        ppu.write_address(0x20);
+        assert_eq!(ppu.read_data(), 0x22);
+        ppu.write_address(0x3f);
+        ppu.write_address(0x30);
+        assert_eq!(ppu.read_data(), 0x22);
+    }
+
+    #[test]
+    fn test_vram_mirror_3000() {
+        let mut ppu = NesPPU::new_empty_rom();
+        ppu.write_address(0x33);
+        ppu.write_address(0x05);
+        ppu.write_data(0x66);
+        assert_eq!(ppu.vram[0x0305], 0x66);
+
+        ppu.vram[0x0306] = 0x77;
+        ppu.write_address(0x33);
+        ppu.write_address(0x06);
+        ppu.read_data();
+        assert_eq!(ppu.read_data(), 0x77);
+    }
+
     #[test]
     fn test_status() {
         let mut ppu = NesPPU::new_empty_rom();
@@ -426,4 +800,320 @@ pub mod test {
         ppu.write_oam_address(0x11);
         assert_eq!(ppu.read_oam_data(), 0x66);
     }
-}
\ No newline at end of file
+
+    // CHR RAM with tile 1 drawn in color 1 and tile 2 in color 2
+    fn rendering_ppu() -> NesPPU {
+        let mut ppu = NesPPU::new(vec![], Mirroring::HORIZONTAL);
+        ppu.write_address(0x00);
+        ppu.write_address(0x10);
+        for data in [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0] {
+            ppu.write_data(data);
+        }
+        for data in [0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff] {
+            ppu.write_data(data);
+        }
+        // $2006 writes share t with the scroll
+        ppu.write_control(0);
+        ppu.write_scroll(0);
+        ppu.write_scroll(0);
+        ppu.palette_table[0] = 0x0f;
+        ppu.palette_table[1] = 0x01;
+        ppu.palette_table[2] = 0x02;
+        return ppu;
+    }
+
+    fn run_to_vblank(ppu: &mut NesPPU) -> usize {
+        let mut dots = 1;
+        while !ppu.tick(1) {
+            dots += 1;
+        }
+        return dots;
+    }
+
+    fn pixel(ppu: &NesPPU, x: usize, y: usize) -> (u8, u8, u8) {
+        let base = (y * 256 + x) * 3;
+        let data = &ppu.frame().data;
+        return (data[base], data[base + 1], data[base + 2]);
+    }
+
+    #[test]
+    fn test_render_background() {
+        let mut ppu = rendering_ppu();
+        ppu.vram[0] = 1;
+        ppu.vram[1] = 2;
+        ppu.write_mask(0b0000_1010);
+        run_to_vblank(&mut ppu);
+        run_to_vblank(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), SYSTEM_PALETTE[0x01]);
+        assert_eq!(pixel(&ppu, 8, 7), SYSTEM_PALETTE[0x02]);
+        assert_eq!(pixel(&ppu, 16, 0), SYSTEM_PALETTE[0x0f]);
+        assert_eq!(pixel(&ppu, 0, 8), SYSTEM_PALETTE[0x0f]);
+    }
+
+    #[test]
+    fn test_render_fine_scroll() {
+        let mut ppu = rendering_ppu();
+        ppu.vram[0] = 1;
+        ppu.vram[1] = 2;
+        ppu.write_scroll(3);
+        ppu.write_scroll(0);
+        ppu.write_mask(0b0000_1010);
+        run_to_vblank(&mut ppu);
+        run_to_vblank(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 4, 0), SYSTEM_PALETTE[0x01]);
+        assert_eq!(pixel(&ppu, 5, 0), SYSTEM_PALETTE[0x02]);
+        assert_eq!(pixel(&ppu, 13, 0), SYSTEM_PALETTE[0x0f]);
+    }
+
+    #[test]
+    fn test_render_mid_frame_palette_change() {
+        let mut ppu = rendering_ppu();
+        for row in 0 .. 30 {
+            ppu.vram[row * 32] = 1;
+        }
+        ppu.write_mask(0b0000_1010);
+        run_to_vblank(&mut ppu);
+        while ppu.scanline() != 120 {
+            ppu.tick(1);
+        }
+        ppu.palette_table[1] = 0x05;
+        run_to_vblank(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 0), SYSTEM_PALETTE[0x01]);
+        assert_eq!(pixel(&ppu, 0, 119), SYSTEM_PALETTE[0x01]);
+        assert_eq!(pixel(&ppu, 0, 120), SYSTEM_PALETTE[0x05]);
+        assert_eq!(pixel(&ppu, 0, 239), SYSTEM_PALETTE[0x05]);
+    }
+
+    #[test]
+    fn test_scroll_registers() {
+        let mut ppu = NesPPU::new_empty_rom();
+        ppu.write_control(0b0000_0011);
+        ppu.read_status();
+        ppu.write_scroll(0x7d);
+        assert_eq!((ppu.scroll.t, ppu.scroll.fine_x), (0x0c0f, 0x05));
+        ppu.write_scroll(0x5e);
+        assert_eq!(ppu.scroll.t, 0x6d6f);
+        ppu.write_address(0x3d);
+        assert_eq!(ppu.scroll.t, 0x3d6f);
+        ppu.write_address(0xf0);
+        assert_eq!((ppu.scroll.t, ppu.scroll.v), (0x3df0, 0x3df0));
+        assert!(!ppu.scroll.write_toggle);
+    }
+
+    #[test]
+    fn test_render_mid_frame_address_write() {
+        let mut ppu = rendering_ppu();
+        ppu.vram[0x0400] = 2; // $2800
+        ppu.write_mask(0b0000_1010);
+        run_to_vblank(&mut ppu);
+        while !(ppu.scanline() == 120 && ppu.dot() == 260) {
+            ppu.tick(1);
+        }
+        // the split takes effect from the next line. bit 13 of $2800 lands
+        // in fine Y, so the tile starts at its third row
+        ppu.write_address(0x28);
+        ppu.write_address(0x00);
+        run_to_vblank(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 0, 120), SYSTEM_PALETTE[0x0f]);
+        assert_eq!(pixel(&ppu, 0, 121), SYSTEM_PALETTE[0x02]);
+        assert_eq!(pixel(&ppu, 7, 126), SYSTEM_PALETTE[0x02]);
+        assert_eq!(pixel(&ppu, 0, 127), SYSTEM_PALETTE[0x0f]);
+    }
+
+    #[test]
+    fn test_render_sprite_zero_hit() {
+        let mut ppu = rendering_ppu();
+        ppu.vram[32 + 2] = 1;
+        ppu.palette_table[0x12] = 0x06;
+        // sprite 0 at x = 16, drawn on the lines after y = 9
+        ppu.oam_data[.. 4].copy_from_slice(&[9, 2, 0x00, 16]);
+        ppu.write_mask(0b0001_1110);
+        run_to_vblank(&mut ppu);
+        run_to_vblank(&mut ppu);
+        assert!(ppu.status.read() & 0b0100_0000 != 0);
+        assert_eq!(pixel(&ppu, 16, 9), SYSTEM_PALETTE[0x01]);
+        assert_eq!(pixel(&ppu, 16, 10), SYSTEM_PALETTE[0x06]);
+        assert_eq!(pixel(&ppu, 23, 17), SYSTEM_PALETTE[0x06]);
+        assert_eq!(pixel(&ppu, 24, 10), SYSTEM_PALETTE[0x0f]);
+        assert_eq!(pixel(&ppu, 16, 18), SYSTEM_PALETTE[0x0f]);
+
+        // cleared on the pre-render line
+        while ppu.scanline() != 0 {
+            ppu.tick(1);
+        }
+        assert!(ppu.status.read() & 0b0100_0000 == 0);
+    }
+
+    // OAM with every sprite below the screen
+    fn sprite_ppu() -> NesPPU {
+        let mut ppu = rendering_ppu();
+        ppu.oam_data = [0xff; 256];
+        ppu.palette_table[0x11] = 0x06;
+        ppu.write_mask(0b0001_1110);
+        return ppu;
+    }
+
+    fn is_sprite_overflow(ppu: &NesPPU) -> bool {
+        return ppu.status.read() & 0b0010_0000 != 0;
+    }
+
+    #[test]
+    fn test_sprite_limit() {
+        let mut ppu = sprite_ppu();
+        for i in 0 .. 9 {
+            ppu.oam_data[i * 4 .. i * 4 + 4].copy_from_slice(&[100, 1, 0x00, i as u8 * 8]);
+        }
+        run_to_vblank(&mut ppu);
+        assert!(is_sprite_overflow(&ppu));
+        assert_eq!(pixel(&ppu, 56, 101), SYSTEM_PALETTE[0x06]);
+        assert_eq!(pixel(&ppu, 64, 101), SYSTEM_PALETTE[0x0f]);
+
+        ppu.sprite_limit = false;
+        run_to_vblank(&mut ppu);
+        assert!(is_sprite_overflow(&ppu));
+        assert_eq!(pixel(&ppu, 64, 101), SYSTEM_PALETTE[0x06]);
+    }
+
+    #[test]
+    fn test_sprite_overflow_false_negative() {
+        let mut ppu = sprite_ppu();
+        for i in 0 .. 8 {
+            ppu.oam_data[i * 4] = 100;
+        }
+        // the 10th sprite is in range, but its tile is compared as Y
+        ppu.oam_data[36 .. 40].copy_from_slice(&[100, 200, 0x00, 0]);
+        run_to_vblank(&mut ppu);
+        assert!(!is_sprite_overflow(&ppu));
+    }
+
+    #[test]
+    fn test_sprite_overflow_false_positive() {
+        let mut ppu = sprite_ppu();
+        for i in 0 .. 8 {
+            ppu.oam_data[i * 4] = 100;
+        }
+        // the 10th sprite is out of range, but its tile looks like a Y in range
+        ppu.oam_data[36 .. 40].copy_from_slice(&[200, 100, 0x00, 0]);
+        run_to_vblank(&mut ppu);
+        assert!(is_sprite_overflow(&ppu));
+
+        // cleared on the pre-render line
+        while ppu.scanline() != 0 {
+            ppu.tick(1);
+        }
+        assert!(!is_sprite_overflow(&ppu));
+    }
+
+    #[test]
+    fn test_render_8x16_sprite() {
+        let mut ppu = sprite_ppu();
+        // tiles 2 and 3 of the $1000 table, drawn in colors 1 and 2
+        for row in 0 .. 8 {
+            ppu.write_chr(0x1020 + row, 0xff);
+            ppu.write_chr(0x1038 + row, 0xff);
+        }
+        ppu.palette_table[0x12] = 0x07;
+        ppu.write_control(ControlRegister::SPRITE_SIZE.bits());
+        ppu.oam_data[.. 8].copy_from_slice(&[100, 0x03, 0x00, 16, 100, 0x03, 0x80, 40]);
+        // only the bottom half of sprite 0 is over the background
+        ppu.vram[14 * 32 + 2] = 1;
+        run_to_vblank(&mut ppu);
+        run_to_vblank(&mut ppu);
+
+        assert_eq!(pixel(&ppu, 16, 101), SYSTEM_PALETTE[0x06]);
+        assert_eq!(pixel(&ppu, 16, 108), SYSTEM_PALETTE[0x06]);
+        assert_eq!(pixel(&ppu, 16, 109), SYSTEM_PALETTE[0x07]);
+        assert_eq!(pixel(&ppu, 16, 116), SYSTEM_PALETTE[0x07]);
+        assert_eq!(pixel(&ppu, 16, 117), SYSTEM_PALETTE[0x01]);
+        // vertical flip swaps the halves
+        assert_eq!(pixel(&ppu, 40, 101), SYSTEM_PALETTE[0x07]);
+        assert_eq!(pixel(&ppu, 40, 109), SYSTEM_PALETTE[0x06]);
+        assert!(ppu.status.read() & 0b0100_0000 != 0);
+    }
+
+    #[test]
+    fn test_render_sprite_priority() {
+        let mut ppu = sprite_ppu();
+        ppu.palette_table[0x15] = 0x08;
+        for column in [2, 5, 8] {
+            ppu.vram[12 * 32 + column] = 1;
+        }
+        ppu.oam_data[.. 16].copy_from_slice(&[
+            99, 1, 0x20, 16, // behind the background
+            99, 1, 0x01, 16, // in front, but after sprite 0
+            99, 1, 0x20, 40, // behind the background
+            99, 1, 0x00, 64, // in front
+        ]);
+        run_to_vblank(&mut ppu);
+        run_to_vblank(&mut ppu);
+
+        // sprite 0 hides sprite 1 where the background covers it
+        assert_eq!(pixel(&ppu, 16, 101), SYSTEM_PALETTE[0x01]);
+        assert_eq!(pixel(&ppu, 16, 105), SYSTEM_PALETTE[0x06]);
+        assert_eq!(pixel(&ppu, 40, 101), SYSTEM_PALETTE[0x01]);
+        assert_eq!(pixel(&ppu, 40, 105), SYSTEM_PALETTE[0x06]);
+        assert_eq!(pixel(&ppu, 64, 101), SYSTEM_PALETTE[0x06]);
+        assert!(ppu.status.read() & 0b0100_0000 != 0);
+    }
+
+    #[test]
+    fn test_odd_frame_skips_a_dot() {
+        let mut ppu = rendering_ppu();
+        run_to_vblank(&mut ppu);
+        assert_eq!(run_to_vblank(&mut ppu), 341 * 262);
+        ppu.write_mask(0b0000_1000);
+        assert_eq!(run_to_vblank(&mut ppu) + run_to_vblank(&mut ppu), 341 * 262 * 2 - 1);
+    }
+
+    struct CountingMapper {
+        edges: usize,
+    }
+
+    impl Mapper for CountingMapper {
+        fn cpu_read(&mut self, _addr: u16) -> u8 { 0 }
+        fn cpu_write(&mut self, _addr: u16, _data: u8) {}
+        fn ppu_read(&mut self, _addr: u16) -> u8 { 0 }
+        fn ppu_write(&mut self, _addr: u16, _data: u8) {}
+        fn mirroring(&self) -> Mirroring { Mirroring::VERTICAL }
+        fn a12_rising_edge(&mut self) { self.edges += 1; }
+    }
+
+    #[test]
+    fn test_a12_rising_edge_per_scanline() {
+        let mapper = Rc::new(RefCell::new(CountingMapper { edges: 0 }));
+        let mut ppu = NesPPU::new_with_mapper(mapper.clone());
+        ppu.write_control(ControlRegister::SPRITE_PATTERN_ADDRESS.bits());
+        ppu.write_mask(0b0001_1000);
+        for _ in 0 .. 341 * 10 / 3 {
+            ppu.tick(3);
+        }
+        assert_eq!(mapper.borrow().edges, 10);
+
+        // no edges while rendering is disabled
+        ppu.write_mask(0);
+        for _ in 0 .. 341 * 10 / 3 {
+            ppu.tick(3);
+        }
+        assert_eq!(mapper.borrow().edges, 10);
+    }
+
+    #[test]
+    fn test_a12_rising_edge_by_address() {
+        let mapper = Rc::new(RefCell::new(CountingMapper { edges: 0 }));
+        let mut ppu = NesPPU::new_with_mapper(mapper.clone());
+        ppu.write_address(0x10);
+        ppu.write_address(0x00);
+        assert_eq!(mapper.borrow().edges, 1);
+        ppu.write_address(0x00);
+        ppu.write_address(0x00);
+        // v only changes with the second write
+        ppu.write_address(0x10);
+        assert_eq!(mapper.borrow().edges, 1);
+        ppu.write_address(0x00);
+        assert_eq!(mapper.borrow().edges, 2);
+    }
+}
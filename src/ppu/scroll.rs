@@ -1,28 +1,126 @@
+// the internal v/t/x/w registers, shared by $2000, $2005 and $2006
+//
+// v and t are 15 bit VRAM addresses laid out as
+// yyy NN YYYYY XXXXX
+// ||| || ||||| +++++- coarse X scroll
+// ||| || +++++------- coarse Y scroll
+// ||| ++------------- name table select
+// +++---------------- fine Y scroll
+//
+// t holds the top-left of the screen between the writes and is copied into v
+// by the second $2006 write and during rendering.
 pub struct ScrollRegister {
-    pub scroll_x: u8,
-    pub scroll_y: u8,
-    pub latch: bool,
+    pub v: u16,
+    pub t: u16,
+    pub fine_x: u8,
+    pub write_toggle: bool,
 }
 
 impl ScrollRegister {
     pub fn new() -> Self {
         ScrollRegister {
-            scroll_x: 0,
-            scroll_y: 0,
-            latch: false,
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            write_toggle: false,
         }
     }
 
-    pub fn update(&mut self, data: u8) {
-        if self.latch {
-            self.scroll_y = data;
+    // $2000 selects the name table
+    pub fn write_control(&mut self, data: u8) {
+        self.t = (self.t & !0x0c00) | ((data as u16 & 0x03) << 10);
+    }
+
+    // $2005, X then Y
+    pub fn write_scroll(&mut self, data: u8) {
+        if !self.write_toggle {
+            self.t = (self.t & !0x001f) | (data as u16 >> 3);
+            self.fine_x = data & 0x07;
+        } else {
+            self.t = (self.t & !0x73e0) | ((data as u16 & 0x07) << 12) | ((data as u16 & 0xf8) << 2);
+        }
+        self.write_toggle = !self.write_toggle;
+    }
+
+    // $2006, high byte then low byte
+    pub fn write_address(&mut self, data: u8) {
+        if !self.write_toggle {
+            self.t = (self.t & 0x00ff) | ((data as u16 & 0x3f) << 8);
+        } else {
+            self.t = (self.t & 0xff00) | data as u16;
+            self.v = self.t;
+        }
+        self.write_toggle = !self.write_toggle;
+    }
+
+    // $2002 reads reset the toggle
+    pub fn reset_latch(&mut self) {
+        self.write_toggle = false;
+    }
+
+    pub fn vram_address(&self) -> u16 {
+        return self.v & 0x3fff;
+    }
+
+    // after a $2007 access outside rendering
+    pub fn increment(&mut self, value: u8) {
+        self.v = self.v.wrapping_add(value as u16) & 0x7fff;
+    }
+
+    pub fn increment_coarse_x(&mut self) {
+        if self.v & 0x001f == 31 {
+            // wrap into the horizontally adjacent name table
+            self.v &= !0x001f;
+            self.v ^= 0x0400;
         } else {
-            self.scroll_x = data;
+            self.v += 1;
         }
-        self.latch = !self.latch;
     }
 
-    pub fn reset(&mut self) {
-        self.latch = false;
+    pub fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03e0) >> 5;
+        if coarse_y == 29 {
+            // wrap into the vertically adjacent name table
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            // attribute rows wrap without switching the name table
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03e0) | coarse_y << 5;
+    }
+
+    // coarse X and the horizontal name table
+    pub fn copy_horizontal(&mut self) {
+        self.v = (self.v & !0x041f) | (self.t & 0x041f);
+    }
+
+    // fine Y, coarse Y and the vertical name table
+    pub fn copy_vertical(&mut self) {
+        self.v = (self.v & !0x7be0) | (self.t & 0x7be0);
+    }
+
+    pub fn fine_y(&self) -> u16 {
+        return self.v >> 12;
+    }
+
+    pub fn name_table_address(&self) -> u16 {
+        return 0x2000 | (self.v & 0x0fff);
+    }
+
+    pub fn attribute_address(&self) -> u16 {
+        return 0x23c0 | (self.v & 0x0c00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07);
+    }
+
+    // quadrant of the 32x32 pixel area by bit 1 of coarse Y and coarse X
+    pub fn attribute_shift(&self) -> u8 {
+        return (((self.v >> 4) & 0x04) | (self.v & 0x02)) as u8;
     }
-}
\ No newline at end of file
+}
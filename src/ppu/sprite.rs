@@ -0,0 +1,23 @@
+// a sprite selected for the scanline, with its row of pattern data
+pub struct Sprite {
+    pub index: u8,
+    pub x: u8,
+    pub palette: u8,
+    pub behind_background: bool,
+    // leftmost pixel in bit 7, already flipped horizontally
+    pub pattern_low: u8,
+    pub pattern_high: u8,
+}
+
+impl Sprite {
+    // value of the pixel at screen column x, 0 is transparent
+    pub fn pixel(&self, x: usize) -> u8 {
+        let offset = x.wrapping_sub(self.x as usize);
+        if offset >= 8 {
+            return 0;
+        }
+        let bit = 0x80 >> offset;
+        return ((self.pattern_high & bit != 0) as u8) << 1 | (self.pattern_low & bit != 0) as u8;
+    }
+}
+
@@ -1,12 +1,14 @@
-// supported iNES 1.0 and mapper 0
+// supported iNES 1.0 and NES 2.0, mappers are in the mapper module
 use bitflags::*;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 #[allow(non_camel_case_types)]
 pub enum Mirroring {
     VERTICAL,
     HORIZONTAL,
     FOUR_SCREEN,
+    SINGLE_SCREEN_LOWER,
+    SINGLE_SCREEN_UPPER,
 }
 
 bitflags! {
@@ -32,17 +34,74 @@ const NUM_16KB_ROM_BANKS: usize = 4;
 const NUM_8KB_VROM_BANKS: usize = 5;
 const NES_CONTROL_BYTE1: usize = 6;
 const NES_CONTROL_BYTE2: usize = 7;
-// const SIZE_PRG_RAM_8KB: usize = 8;
+const NUM_8KB_RAM_BANKS: usize = 8;
+// NES 2.0
+const MAPPER_MSB_SUBMAPPER: usize = 8;
+const ROM_SIZE_MSB: usize = 9;
+const PRG_RAM_SHIFT: usize = 10;
+const CHR_RAM_SHIFT: usize = 11;
+const CPU_PPU_TIMING: usize = 12;
+const EXTENDED_CONSOLE_TYPE: usize = 13;
+const DEFAULT_EXPANSION_DEVICE: usize = 15;
 
 const NES_TAG: [u8; 4] = [0x4e, 0x45, 0x53, 0x1a];
-const PRG_ROM_PAGE_SIZE: usize = 16384;
-const CHR_ROM_PAGE_SIZE: usize = 8192;
+pub const PRG_ROM_PAGE_SIZE: usize = 16384;
+pub const CHR_ROM_PAGE_SIZE: usize = 8192;
+const PRG_RAM_PAGE_SIZE: usize = 8192;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Timing {
+    NTSC,
+    PAL,
+    MULTI_REGION,
+    DENDY,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum ConsoleType {
+    NES,
+    VS_SYSTEM,
+    PLAYCHOICE_10,
+    EXTENDED(u8),
+}
 
 pub struct Rom {
     pub prg_rom: Vec<u8>,
     pub chr_rom: Vec<u8>,
-    pub mapper: u8,
+    pub mapper: u16,
+    pub submapper: u8,
     pub screen_mirroring: Mirroring,
+    pub prg_ram_size: usize,   // volatile
+    pub prg_nvram_size: usize, // battery backed
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub battery: bool,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    pub expansion_device: u8,
+}
+
+// NES 2.0 ROM size, either a count of pages or an exponent-multiplier
+fn nes2_rom_size(lsb: u8, msb: u8, page_size: usize) -> Result<usize, String> {
+    if msb == 0x0f {
+        // the exponent goes up to 2^63, which a malformed header can overflow
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = ((lsb & 0x03) * 2 + 1) as usize;
+        return 1usize.checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+            .ok_or("ROM size is out of range".to_string());
+    }
+    return Ok((((msb as usize) << 8) | lsb as usize) * page_size);
+}
+
+// NES 2.0 RAM size, 64 << shift bytes, 0 means none
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        return 0;
+    }
+    return 64 << shift;
 }
 
 impl Rom {
@@ -51,21 +110,31 @@ impl Rom {
             prg_rom: vec![0; 16384],
             chr_rom: vec![0; 16384],
             mapper: 0,
+            submapper: 0,
             screen_mirroring: Mirroring::VERTICAL,
+            prg_ram_size: PRG_RAM_PAGE_SIZE,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            battery: false,
+            timing: Timing::NTSC,
+            console_type: ConsoleType::NES,
+            expansion_device: 0,
         })
     }
-    pub fn new(raw: &Vec<u8>) -> Result<Rom, String> {
-        if &raw[NES_HEADER .. NUM_16KB_ROM_BANKS] != NES_TAG {
+    pub fn new(raw: &[u8]) -> Result<Rom, String> {
+        if raw.len() < 16 || raw[NES_HEADER .. NUM_16KB_ROM_BANKS] != NES_TAG {
             return Err("not iNES format".to_string());
         }
         let nes_control_byte1: INesControlByte1 = INesControlByte1::from_bits(raw[ NES_CONTROL_BYTE1 ]).unwrap();
         let nes_control_byte2: INesControlByte2 = INesControlByte2::from_bits(raw[ NES_CONTROL_BYTE2 ]).unwrap();
-        let mapper = (nes_control_byte2 & INesControlByte2::MAPPER_TYPE_HIGHT).bits | ((nes_control_byte1 & INesControlByte1::MAPPER_TYPE_LOW).bits >> 4);
+        let mut mapper = ((nes_control_byte2 & INesControlByte2::MAPPER_TYPE_HIGHT).bits | ((nes_control_byte1 & INesControlByte1::MAPPER_TYPE_LOW).bits >> 4)) as u16;
         let ines_ver = (nes_control_byte2 & INesControlByte2::INES_VERSION).bits >> 2;
-
-        if ines_ver != 0 {
-            return Err("not iNES 1.0".to_string());
-        }
+        let nes2 = match ines_ver {
+            0 => false,
+            2 => true,
+            _ => return Err("unknown iNES version".to_string()),
+        };
 
         let four_screen = nes_control_byte1.contains(INesControlByte1::FOUR_SCREEN_LAYOUT);
         let vertical_mirroring = nes_control_byte1.contains(INesControlByte1::VERTICAL_MIRRORING);
@@ -74,18 +143,74 @@ impl Rom {
             (false, true) => Mirroring::VERTICAL,
             (false, false) => Mirroring::HORIZONTAL,
         };
+        let battery = nes_control_byte1.contains(INesControlByte1::BATTERY_PACKED_RAM);
+
+        let mut submapper = 0;
+        let mut timing = Timing::NTSC;
+        let mut console_type = match (nes_control_byte2 & INesControlByte2::INES_VERSION_RESERVE).bits {
+            0 => ConsoleType::NES,
+            1 => ConsoleType::VS_SYSTEM,
+            _ => ConsoleType::PLAYCHOICE_10,
+        };
+        let mut expansion_device = 0;
+        let prg_rom_size;
+        let chr_rom_size;
+        let prg_ram_size;
+        let prg_nvram_size;
+        let chr_ram_size;
+        let chr_nvram_size;
+        if nes2 {
+            mapper |= ((raw[MAPPER_MSB_SUBMAPPER] & 0x0f) as u16) << 8;
+            submapper = raw[MAPPER_MSB_SUBMAPPER] >> 4;
+            prg_rom_size = nes2_rom_size(raw[NUM_16KB_ROM_BANKS], raw[ROM_SIZE_MSB] & 0x0f, PRG_ROM_PAGE_SIZE)?;
+            chr_rom_size = nes2_rom_size(raw[NUM_8KB_VROM_BANKS], raw[ROM_SIZE_MSB] >> 4, CHR_ROM_PAGE_SIZE)?;
+            prg_ram_size = nes2_ram_size(raw[PRG_RAM_SHIFT] & 0x0f);
+            prg_nvram_size = nes2_ram_size(raw[PRG_RAM_SHIFT] >> 4);
+            chr_ram_size = nes2_ram_size(raw[CHR_RAM_SHIFT] & 0x0f);
+            chr_nvram_size = nes2_ram_size(raw[CHR_RAM_SHIFT] >> 4);
+            timing = match raw[CPU_PPU_TIMING] & 0x03 {
+                0 => Timing::NTSC,
+                1 => Timing::PAL,
+                2 => Timing::MULTI_REGION,
+                _ => Timing::DENDY,
+            };
+            if (nes_control_byte2 & INesControlByte2::INES_VERSION_RESERVE).bits == 3 {
+                console_type = ConsoleType::EXTENDED(raw[EXTENDED_CONSOLE_TYPE] & 0x0f);
+            }
+            expansion_device = raw[DEFAULT_EXPANSION_DEVICE] & 0x3f;
+        } else {
+            prg_rom_size = raw[NUM_16KB_ROM_BANKS] as usize * PRG_ROM_PAGE_SIZE;
+            chr_rom_size = raw[NUM_8KB_VROM_BANKS] as usize * CHR_ROM_PAGE_SIZE;
+            // 0 means 8KB for compatibility, all of it battery backed if the flag is set
+            let ram_size = (raw[NUM_8KB_RAM_BANKS] as usize).max(1) * PRG_RAM_PAGE_SIZE;
+            prg_ram_size = if battery { 0 } else { ram_size };
+            prg_nvram_size = if battery { ram_size } else { 0 };
+            chr_ram_size = if chr_rom_size == 0 { CHR_ROM_PAGE_SIZE } else { 0 };
+            chr_nvram_size = 0;
+        }
 
-        let prg_rom_size = (raw[NUM_16KB_ROM_BANKS] as usize * PRG_ROM_PAGE_SIZE) as usize;
-        let chr_rom_size = (raw[NUM_8KB_VROM_BANKS] as usize * CHR_ROM_PAGE_SIZE) as usize;
         let skip_trainer = nes_control_byte1.contains(INesControlByte1::TRAINER);
         let prg_rom_start = (16 + if skip_trainer { 512 } else { 0 }) as usize;
-        let chr_rom_start = prg_rom_start + prg_rom_size;
+        let chr_rom_start = prg_rom_start.checked_add(prg_rom_size).ok_or("ROM size is out of range".to_string())?;
+        let rom_end = chr_rom_start.checked_add(chr_rom_size).ok_or("ROM size is out of range".to_string())?;
+        if raw.len() < rom_end {
+            return Err("ROM image is truncated".to_string());
+        }
 
         return Ok( Rom {
             prg_rom: raw[prg_rom_start .. (prg_rom_start + prg_rom_size)].to_vec(),
             chr_rom: raw[chr_rom_start .. (chr_rom_start + chr_rom_size)].to_vec(),
             mapper: mapper,
+            submapper: submapper,
             screen_mirroring: screen_mirroring,
+            prg_ram_size: prg_ram_size,
+            prg_nvram_size: prg_nvram_size,
+            chr_ram_size: chr_ram_size,
+            chr_nvram_size: chr_nvram_size,
+            battery: battery,
+            timing: timing,
+            console_type: console_type,
+            expansion_device: expansion_device,
         });
     }
 }
@@ -117,12 +242,21 @@ pub mod test {
         return result;
     }
 
+    pub fn create_test_rom_with_prg(prg_rom: Vec<u8>) -> Vec<u8> {
+        return create_test_rom(TestRom{
+            header: vec![ 0x4e, 0x45, 0x53, 0x1a, (prg_rom.len() / PRG_ROM_PAGE_SIZE) as u8, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0,],
+            trainer: None,
+            prg_rom: prg_rom,
+            chr_rom: vec![0; CHR_ROM_PAGE_SIZE],
+        });
+    }
+
     pub fn test_rom() -> Rom {
         let test_rom = create_test_rom(TestRom{
-            header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x02, 0x01, 0x31, 0, 0, 0, 0, 0, 0, 0, 0, 0,],
+            header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x02, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0,],
             trainer: None,
             prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
-            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
         });
         return Rom::new(&test_rom).unwrap();
     }
@@ -133,14 +267,32 @@ pub mod test {
             header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x02, 0x01, 0x31, 0, 0, 0, 0, 0, 0, 0, 0, 0,],
             trainer: None,
             prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
-            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
         });
         let rom = Rom::new(&test_rom).unwrap();
 
         assert_eq!(rom.prg_rom, vec![1; 2 * PRG_ROM_PAGE_SIZE]);
-        assert_eq!(rom.chr_rom, vec![2; 1 * CHR_ROM_PAGE_SIZE]);
+        assert_eq!(rom.chr_rom, vec![2; CHR_ROM_PAGE_SIZE]);
         assert_eq!(rom.mapper, 3);
         assert_eq!(rom.screen_mirroring, Mirroring::VERTICAL);
+        assert_eq!(rom.prg_ram_size, PRG_RAM_PAGE_SIZE);
+        assert!(!rom.battery);
+    }
+
+    #[test]
+    fn test_battery() {
+        let test_rom = create_test_rom(TestRom{
+            header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x02, 0x01, 0x13, 0, 0x04, 0, 0, 0, 0, 0, 0, 0,],
+            trainer: None,
+            prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+        });
+        let rom = Rom::new(&test_rom).unwrap();
+
+        assert_eq!(rom.mapper, 1);
+        assert_eq!(rom.prg_ram_size, 0);
+        assert_eq!(rom.prg_nvram_size, 4 * PRG_RAM_PAGE_SIZE);
+        assert!(rom.battery);
     }
 
     #[test]
@@ -149,28 +301,86 @@ pub mod test {
             header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x02, 0x01, (0x31 | 0x04), 0, 0, 0, 0, 0, 0, 0, 0, 0,],
             trainer: Some(vec![0; 512]),
             prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
-            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
         });
         let rom = Rom::new(&test_rom).unwrap();
 
         assert_eq!(rom.prg_rom, vec![1; 2 * PRG_ROM_PAGE_SIZE]);
-        assert_eq!(rom.chr_rom, vec![2; 1 * CHR_ROM_PAGE_SIZE]);
+        assert_eq!(rom.chr_rom, vec![2; CHR_ROM_PAGE_SIZE]);
         assert_eq!(rom.mapper, 3);
         assert_eq!(rom.screen_mirroring, Mirroring::VERTICAL);
     }
 
     #[test]
-    fn test_unsupported_ines20() {
+    fn test_ines20() {
         let test_rom = create_test_rom(TestRom{
-            header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x01, 0x01, 0x31, 8, 0, 0, 0, 0, 0, 0, 0, 0,],
+            header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x02, 0x00, 0x13, 0x48, 0x21, 0x00, 0x70, 0x07, 0x01, 0, 0, 0x01,],
             trainer: None,
             prg_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
-            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+            chr_rom: vec![],
+        });
+        let rom = Rom::new(&test_rom).unwrap();
+
+        assert_eq!(rom.prg_rom, vec![1; 2 * PRG_ROM_PAGE_SIZE]);
+        assert!(rom.chr_rom.is_empty());
+        assert_eq!(rom.mapper, 0x141);
+        assert_eq!(rom.submapper, 2);
+        assert_eq!(rom.screen_mirroring, Mirroring::VERTICAL);
+        assert_eq!(rom.prg_ram_size, 0);
+        assert_eq!(rom.prg_nvram_size, 0x2000);
+        assert_eq!(rom.chr_ram_size, 0x2000);
+        assert_eq!(rom.chr_nvram_size, 0);
+        assert!(rom.battery);
+        assert_eq!(rom.timing, Timing::PAL);
+        assert_eq!(rom.console_type, ConsoleType::NES);
+        assert_eq!(rom.expansion_device, 1);
+    }
+
+    #[test]
+    fn test_ines20_exponent_size() {
+        // PRG ROM 2^14 * 3 = 48KB, CHR ROM 2^13 * 1 = 8KB
+        let test_rom = create_test_rom(TestRom{
+            header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x39, 0x34, 0x00, 0x0b, 0x00, 0xff, 0, 0, 0, 0x03, 0, 0,],
+            trainer: None,
+            prg_rom: vec![1; 3 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; CHR_ROM_PAGE_SIZE],
+        });
+        let rom = Rom::new(&test_rom).unwrap();
+
+        assert_eq!(rom.prg_rom, vec![1; 3 * PRG_ROM_PAGE_SIZE]);
+        assert_eq!(rom.chr_rom, vec![2; CHR_ROM_PAGE_SIZE]);
+        assert_eq!(rom.console_type, ConsoleType::EXTENDED(3));
+    }
+
+    #[test]
+    fn test_ines20_exponent_size_overflow() {
+        // PRG ROM 2^63 * 3, then 2^63 of both
+        for (prg, chr) in [(0xfd, 0x00), (0xfc, 0xfc)] {
+            let test_rom = create_test_rom(TestRom{
+                header: vec![ 0x4e, 0x45, 0x53, 0x1a, prg, chr, 0x00, 0x08, 0x00, 0xff, 0, 0, 0, 0, 0, 0,],
+                trainer: None,
+                prg_rom: vec![1; 16],
+                chr_rom: vec![],
+            });
+            match Rom::new(&test_rom) {
+                Result::Ok(_) => panic!("unexpected ROM size"),
+                Result::Err(str) => assert_eq!(str, "ROM size is out of range"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncated() {
+        let test_rom = create_test_rom(TestRom{
+            header: vec![ 0x4e, 0x45, 0x53, 0x1a, 0x02, 0x01, 0x31, 0, 0, 0, 0, 0, 0, 0, 0, 0,],
+            trainer: None,
+            prg_rom: vec![1; PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![],
         });
         let rom = Rom::new(&test_rom);
         match rom {
-            Result::Ok(_) => assert!(false, "unexpected support ines2.0"),
-            Result::Err(str) => assert_eq!(str, "not iNES 1.0"),
+            Result::Ok(_) => panic!("unexpected truncated ROM"),
+            Result::Err(str) => assert_eq!(str, "ROM image is truncated"),
         }
     }
 
@@ -1,38 +1,20 @@
-pub mod bus;
-pub mod rom;
-pub mod cpu;
-pub mod opcodes;
-pub mod trace;
-pub mod ppu;
-pub mod renderer;
-pub mod joypad;
-
-// use bus::Bus;
-use rom::Rom;
-// use cpu::CPU;
-// use bus::Memory;
-// use trace::trace;
-use renderer::Frame;
-use renderer::SYSTEM_PALETTE;
+use nesemulator::Nes;
+use nesemulator::rom::Rom;
+use nesemulator::renderer::Frame;
+use nesemulator::renderer::SYSTEM_PALETTE;
 
 use std::env;
 use getopts::Options;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-// use sdl2::pixels::Color;
 use sdl2::pixels::PixelFormatEnum;
-// use sdl2::EventPump;
 
-#[macro_use]
-extern crate lazy_static;
-// #[macro_use]
-// extern crate bitflags;
-
-fn show_tile(chr_rom: &Vec<u8>, bank: usize, tile_n: usize) ->Frame {
+#[allow(dead_code)]
+fn show_tile(chr_rom: &[u8], bank: usize, tile_n: usize) ->Frame {
     assert!(bank <= 1);
 
     let mut frame = Frame::new();
-    let bank = (bank * 0x1000) as usize;
+    let bank = bank * 0x1000;
 
     let tile = &chr_rom[(bank + tile_n * 16)..=(bank + tile_n * 16 + 15)];
 
@@ -42,8 +24,8 @@ fn show_tile(chr_rom: &Vec<u8>, bank: usize, tile_n: usize) ->Frame {
 
         for x in (0..=7).rev() {
             let value = (1 & upper) << 1 | (1 & lower);
-            upper = upper >> 1;
-            lower = lower >> 1;
+            upper >>= 1;
+            lower >>= 1;
             let rgb = match value {
                 0 => SYSTEM_PALETTE[0x01],
                 1 => SYSTEM_PALETTE[0x23],
@@ -59,13 +41,13 @@ fn show_tile(chr_rom: &Vec<u8>, bank: usize, tile_n: usize) ->Frame {
 }
 
 
-fn show_tile_bank(chr_rom: &Vec<u8>, bank: usize) ->Frame {
+fn show_tile_bank(chr_rom: &[u8], bank: usize) ->Frame {
     assert!(bank <= 1);
 
     let mut frame = Frame::new();
     let mut tile_y = 0;
     let mut tile_x = 0;
-    let bank = (bank * 0x1000) as usize;
+    let bank = bank * 0x1000;
 
     for tile_n in 0..255 {
         if tile_n != 0 && tile_n % 20 == 0 {
@@ -80,8 +62,8 @@ fn show_tile_bank(chr_rom: &Vec<u8>, bank: usize) ->Frame {
 
             for x in (0..=7).rev() {
                 let value = (1 & upper) << 1 | (1 & lower);
-                upper = upper >> 1;
-                lower = lower >> 1;
+                upper >>= 1;
+                lower >>= 1;
                 let rgb = match value {
                     0 => SYSTEM_PALETTE[0x01],
                     1 => SYSTEM_PALETTE[0x23],
@@ -98,6 +80,19 @@ fn show_tile_bank(chr_rom: &Vec<u8>, bank: usize) ->Frame {
     frame
 }
 
+// CHR RAM is empty until the game uploads its tiles,
+// so run the game for a while and read the pattern tables through the PPU
+fn read_chr_ram(bytes: &[u8], frames: usize) -> Vec<u8> {
+    let mut nes = Nes::new();
+    nes.load_rom(bytes).unwrap();
+    for _ in 0..frames {
+        if !nes.run_frame() {
+            break;
+        }
+    }
+    return (0..0x2000).map(|addr| nes.ppu().read_chr(addr)).collect();
+}
+
 fn print_usage(program: &str, opts: Options) {
     let brief = format!("Usage: {} <iNes 1.0 ROM>", program);
     print!("{}", opts.usage(&brief));
@@ -109,6 +104,7 @@ fn main() {
 
     let mut opts = Options::new();
     opts.optflag("h", "help", "print this help menu");
+    opts.optopt("f", "frames", "frames to run before reading CHR RAM (default: 60)", "FRAMES");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m },
         Err(f) => { panic!("{}",f.to_string()); },
@@ -122,6 +118,10 @@ fn main() {
         return;
     }
     let rom_filename = matches.free[0].clone().to_string();
+    let frames = match matches.opt_str("f") {
+        Some(f) => f.parse::<usize>().unwrap(),
+        None => 60,
+    };
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
@@ -143,7 +143,8 @@ fn main() {
     let bytes: Vec<u8> = std::fs::read(&rom_filename).unwrap();
     let rom = Rom::new(&bytes).unwrap();
 
-    let right_bank = show_tile_bank(&rom.chr_rom, 1);
+    let chr = if rom.chr_rom.is_empty() { read_chr_ram(&bytes, frames) } else { rom.chr_rom };
+    let right_bank = show_tile_bank(&chr, 1);
 
     loop {
         texture.update(None, &right_bank.data, 256 * 3).unwrap();
@@ -2,13 +2,19 @@ use crate::cpu::CPU;
 use crate::cpu::AddressingMode;
 use crate::bus::Memory;
 use crate::opcodes;
-use crate::joypad::Joypad;
-use std::collections::HashMap;
+
+// reading I/O registers has side effects (e.g. $2002 clears vblank),
+// so the trace shows them as FF like the nestest log does
+fn peek(cpu: &mut CPU, addr: u16) -> u8 {
+    match addr {
+        0x2000 ..= 0x401F => 0xFF,
+        _ => cpu.memory_read_u8(addr),
+    }
+}
 
 pub fn trace(cpu: &mut CPU) -> String {
-    let ref opcodes: HashMap<u8, &'static opcodes::OpCode> = *opcodes::OPCODE_MAP;
     let code = cpu.memory_read_u8(cpu.reg_pc);
-    let ops = opcodes.get(&code).unwrap();
+    let ops = &opcodes::OPCODE_TABLE[code as usize];
 
     let begin  = cpu.reg_pc;
     let mut dump = vec![];
@@ -18,12 +24,12 @@ pub fn trace(cpu: &mut CPU) -> String {
         AddressingMode::Immediate | AddressingMode::NonAddressing => (0, 0),
         _ => {
             let abs_addr = cpu.get_absolute_address(&ops.mode, begin + 1);
-            (abs_addr, cpu.memory_read_u8(abs_addr))
+            (abs_addr, peek(cpu, abs_addr))
         },
     };
     let operand_string = match ops.len {
         1 => match ops.code {
-            0x0a | 0x4a | 0x2a | 0x6a => format!("A "),
+            0x0a | 0x4a | 0x2a | 0x6a => String::from("A "),
             _ => String::from(""),
         },
         2 => {
@@ -73,20 +79,21 @@ pub fn trace(cpu: &mut CPU) -> String {
     let hex_string = dump.iter().map(|z| format!("{:02x}", z)).collect::<Vec<String>>().join(" ");
     let asm_string = format!("{:04x}  {:8} {: >4} {}", begin, hex_string, ops.mnemonic, operand_string).trim().to_string();
 
-    format!("{:47} A:{:02x} X:{:02x} Y:{:02x} P:{:02x} SP:{:02x}",
-                asm_string, cpu.reg_a, cpu.reg_x, cpu.reg_y, cpu.status, cpu.reg_sp).to_ascii_uppercase()
+    let ppu = cpu.bus.ppu();
+    format!("{:47} A:{:02x} X:{:02x} Y:{:02x} P:{:02x} SP:{:02x} PPU:{:3},{:3} CYC:{}",
+                asm_string, cpu.reg_a, cpu.reg_x, cpu.reg_y, cpu.status, cpu.reg_sp,
+                ppu.scanline(), ppu.dot(), cpu.bus.cycles()).to_ascii_uppercase()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::bus::Bus;
-    use crate::ppu::NesPPU;
     use crate::rom::test::test_rom;
 
     #[test]
     fn test_format_trace() {
-        let mut bus = Bus::new_with_rom(test_rom(), |_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let mut bus = Bus::new_with_rom(test_rom()).unwrap();
         bus.memory_write_u8(100, 0xa2);
         bus.memory_write_u8(101, 0x01);
         bus.memory_write_u8(102, 0xca);
@@ -98,34 +105,35 @@ mod test {
         cpu.reg_a = 1;
         cpu.reg_x = 2;
         cpu.reg_y = 3;
+        cpu.stop_on_brk = true;
         let mut result: Vec<String> = vec![];
         cpu.run_with_callback(|cpu| {
             result.push(trace(cpu));
         });
         assert_eq!(
-            "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD",
+            "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD PPU:  0,  0 CYC:0",
             result[0]
         );
         assert_eq!(
-            "0066  CA        DEX                             A:01 X:01 Y:03 P:24 SP:FD",
+            "0066  CA        DEX                             A:01 X:01 Y:03 P:24 SP:FD PPU:  0,  6 CYC:2",
             result[1]
         );
         assert_eq!(
-            "0067  88        DEY                             A:01 X:00 Y:03 P:26 SP:FD",
+            "0067  88        DEY                             A:01 X:00 Y:03 P:26 SP:FD PPU:  0, 12 CYC:4",
             result[2]
         );
     }
 
     #[test]
     fn test_format_mem_access() {
-        let mut bus = Bus::new_with_rom(test_rom(), |_ppu: &NesPPU, _joypad: &mut Joypad|{});
+        let mut bus = Bus::new_with_rom(test_rom()).unwrap();
         // ORA ($33), Y
         bus.memory_write_u8(100, 0x11);
         bus.memory_write_u8(101, 0x33);
 
         //data
-        bus.memory_write_u8(0x33, 00);
-        bus.memory_write_u8(0x34, 04);
+        bus.memory_write_u8(0x33, 0x00);
+        bus.memory_write_u8(0x34, 0x04);
 
         //target cell
         bus.memory_write_u8(0x400, 0xAA);
@@ -133,12 +141,13 @@ mod test {
         let mut cpu = CPU::new(bus);
         cpu.reg_pc = 0x64;
         cpu.reg_y = 0;
+        cpu.stop_on_brk = true;
         let mut result: Vec<String> = vec![];
         cpu.run_with_callback(|cpu| {
             result.push(trace(cpu));
         });
         assert_eq!(
-            "0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:24 SP:FD",
+            "0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:24 SP:FD PPU:  0,  0 CYC:0",
             result[0]
         );
     }
@@ -0,0 +1,36 @@
+use nesemulator::bus::FlatRam;
+use nesemulator::cpu::CPU;
+
+// runs Klaus Dormann's 6502_functional_test.bin on a flat 64KB RAM.
+// the binary is not part of the repository:
+//   KLAUS_FUNCTIONAL_TEST=<path to the 64KB image, assembled with disable_decimal = 1
+//                          since the NES CPU has no decimal mode>
+//   KLAUS_SUCCESS=<address of the success trap in the listing, hex>
+//   cargo test --test klaus -- --ignored
+const START: u16 = 0x0400;
+const INSTRUCTION_LIMIT: usize = 100_000_000;
+
+#[test]
+#[ignore]
+fn test_klaus_functional() {
+    let path = std::env::var("KLAUS_FUNCTIONAL_TEST").expect("KLAUS_FUNCTIONAL_TEST is not set");
+    let success = std::env::var("KLAUS_SUCCESS").expect("KLAUS_SUCCESS is not set");
+    let success = u16::from_str_radix(success.trim_start_matches("0x"), 16).expect("invalid KLAUS_SUCCESS");
+    let image = std::fs::read(path).unwrap();
+
+    let mut ram = FlatRam::new();
+    ram.load(0x0000, &image);
+    let mut cpu = CPU::new(ram);
+    cpu.reg_pc = START;
+
+    // every test ends in a jump to itself, either a failure or the success trap
+    for _ in 0 .. INSTRUCTION_LIMIT {
+        let pc = cpu.reg_pc;
+        assert!(cpu.step(), "CPU halted at ${:04X}", cpu.reg_pc);
+        if cpu.reg_pc == pc {
+            assert_eq!(pc, success, "trapped at ${:04X}", pc);
+            return;
+        }
+    }
+    panic!("no trap after {} instructions", INSTRUCTION_LIMIT);
+}
@@ -0,0 +1,55 @@
+use nesemulator::bus::Bus;
+use nesemulator::cpu::CPU;
+use nesemulator::rom::Rom;
+use nesemulator::trace::trace;
+
+// runs resource/nestest.nes in automation mode (from $C000) and compares
+// each trace line with resource/nestest.log
+const CONTEXT_LINES: usize = 5;
+
+fn registers_only(line: &str) -> &str {
+    match line.find(" PPU:") {
+        Some(index) => &line[.. index],
+        None => line,
+    }
+}
+
+fn run_nestest(columns: fn(&str) -> &str) {
+    let raw = std::fs::read("resource/nestest.nes").unwrap();
+    let log = std::fs::read_to_string("resource/nestest.log").unwrap();
+    let rom = Rom::new(&raw).unwrap();
+    let bus = Bus::new_with_rom(rom).unwrap();
+    let mut cpu = CPU::new(bus);
+    cpu.reset();
+    cpu.reg_pc = 0xc000;
+    cpu.stop_on_brk = true;
+
+    let mut history: Vec<String> = Vec::new();
+    for (number, expected) in log.lines().enumerate() {
+        let actual = trace(&mut cpu);
+        if columns(&actual) != columns(expected) {
+            let start = history.len().saturating_sub(CONTEXT_LINES);
+            let mut message = format!("diverged at line {}\n", number + 1);
+            for line in &history[start ..] {
+                message += &format!("           {}\n", line);
+            }
+            message += &format!("expected:  {}\n", expected);
+            message += &format!("actual:    {}\n", actual);
+            panic!("{}", message);
+        }
+        history.push(actual);
+        if !cpu.step() {
+            assert_eq!(number + 1, log.lines().count(), "BRK at line {}", number + 1);
+        }
+    }
+}
+
+#[test]
+fn test_nestest_registers() {
+    run_nestest(registers_only);
+}
+
+#[test]
+fn test_nestest_cycles() {
+    run_nestest(|line| line);
+}